@@ -22,6 +22,7 @@ pub fn main() {
     Runtime::block_on(async move {
         let object_ref = future.await;
         let result = Js::invoke("return {}.name", &[object_ref.into()]).to_str().unwrap();
+        Js::deallocate(object_ref);
         Js::invoke("console.log('invoke fetch', {})", &[result.into()]);
     });
 }
@@ -0,0 +1,16 @@
+
+use tinyweb::router::generate;
+use tinyweb::signals::{provide_context, Scope};
+
+use features::{pages, RouterContext};
+
+// renders the pages to html, eg. `cargo run --bin generate /tmp/public`
+fn main() {
+    let dir = std::env::args().nth(1).unwrap_or("dist".to_owned());
+    let head = r#"<link rel="stylesheet" href="https://cdn.jsdelivr.net/npm/tailwindcss@2.2.19/dist/tailwind.min.css">"#;
+    let files = Scope::root().run(|| {
+        provide_context(RouterContext::default());
+        generate(&dir, &pages(), "features.wasm", head)
+    });
+    files.unwrap().iter().for_each(|f| println!("{}", f));
+}
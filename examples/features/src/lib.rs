@@ -2,38 +2,40 @@
 mod keycodes;
 
 use std::cell::RefCell;
+use std::rc::Rc;
 
 use json::JsonValue;
 
-use tinyweb::callbacks::create_async_callback;
 use tinyweb::router::{Page, Router};
-use tinyweb::runtime::Runtime;
-use tinyweb::signals::Signal;
+use tinyweb::runtime::{CancellationToken, Runtime};
+use tinyweb::signals::{provide_context, use_context, Scope, Signal};
 use tinyweb::element::El;
+use tinyweb::frame;
+use tinyweb::worker::{Worker, WorkerScope};
 
 use tinyweb::invoke::*;
 
 const BUTTON_CLASSES: &[&str] = &["bg-blue-500", "hover:bg-blue-700", "text-white", "p-2", "rounded", "m-2"];
 
-thread_local! {
-    pub static ROUTER: RefCell<Router> = RefCell::new(Router::default());
-}
+pub type RouterContext = Rc<RefCell<Router>>;
 
-async fn fetch_json(method: &str, url: &str, body: Option<JsonValue>) -> Result<JsonValue, String> {
+async fn fetch_json(method: &str, url: &str, body: Option<JsonValue>, token: &CancellationToken) -> Result<JsonValue, String> {
     let body = body.map(|s| s.dump()).unwrap_or_default();
-    let (callback_ref, future) = create_async_callback();
     let request = r#"
-        const options = { method: {}, headers: { 'Content-Type': 'application/json' }, body: p0 !== 'GET' ? {} : null };
+        const options = { method: {}, headers: { 'Content-Type': 'application/json' }, body: p0 !== 'GET' ? {} : null, signal: {} };
         fetch({}, options).then(r => r.json()).then(r => { {}(r) })
     "#;
-    Js::invoke(request, &[method.into(), body.into(), url.into(), callback_ref.into()]);
-    let result_ref = future.await;
+    let params_fn = |c: ObjectRef| vec![method.into(), body.into(), token.signal().into(), url.into(), c.into()];
+    let result_ref = token.promise(request, params_fn).await.map_err(|_| "Cancelled".to_owned())?;
     let result = Js::invoke("return JSON.stringify({})", &[result_ref.into()]).to_str().unwrap();
+    Js::deallocate(result_ref);
     json::parse(&result).map_err(|_| "Parse error".to_owned())
 }
 
 fn page1() -> El {
 
+    let router = use_context::<RouterContext>().unwrap();
+
     // signals
     let signal_key = Signal::new("-".to_owned());
     let signal_count = Signal::new(0);
@@ -52,57 +54,92 @@ fn page1() -> El {
                 signal_key.set(text);
             });
 
-            // start timer
-            Runtime::block_on(async move {
-                loop {
-                    signal_time.set("⏰ tik");
-                    Runtime::promise("window.setTimeout({},{})", move |c| vec![c.into(), 1_000.into()]).await;
-                    signal_time.set("⏰ tok");
-                    Runtime::promise("window.setTimeout({},{})", move |c| vec![c.into(), 1_000.into()]).await;
-                }
-            });
-
+        })
+        .spawn(move |_| async move {
+
+            // start timer (stops when the page unmounts)
+            loop {
+                signal_time.set("⏰ tik");
+                Js::deallocate(Runtime::promise("window.setTimeout({},{})", move |c| vec![c.into(), 1_000.into()]).await);
+                signal_time.set("⏰ tok");
+                Js::deallocate(Runtime::promise("window.setTimeout({},{})", move |c| vec![c.into(), 1_000.into()]).await);
+            }
         })
         .classes(&["m-2"])
-        .child(El::new("button").text("api").classes(&BUTTON_CLASSES).on_async("click", move |_| async {
-            let url = format!("https://pokeapi.co/api/v2/pokemon/{}", 1);
-            let result = fetch_json("GET", &url, None).await.unwrap();
-            let name = result["name"].as_str().unwrap();
-            Js::invoke("alert({})", &[name.into()]);
+        .child({
+            // NOTE: the request is aborted if the page is navigated away
+            let button = El::new("button").text("api").classes(&BUTTON_CLASSES);
+            let token = button.token.clone();
+            button.on_async("click", move |_| {
+                let token = token.clone();
+                async move {
+                    let url = format!("https://pokeapi.co/api/v2/pokemon/{}", 1);
+                    let Ok(result) = fetch_json("GET", &url, None, &token).await else { return; };
+                    let name = result["name"].as_str().unwrap();
+                    Js::invoke("alert({})", &[name.into()]);
+                }
+            })
+        })
+        .child(El::new("button").text("worker").classes(&BUTTON_CLASSES).on_async("click", move |_| async {
+            let mut worker = Worker::<String, String>::spawn("worker_main");
+            worker.send("100000".to_owned());
+            let count = worker.recv().await.unwrap();
+            Js::invoke("alert({})", &[format!("Primes: {}", count).into()]);
         }))
         .child(El::new("button").text("page 2").classes(&BUTTON_CLASSES).on("click", move |_| {
-            ROUTER.with(|s| { s.borrow().navigate("/page2"); });
+            router.borrow().navigate("/page2");
         }))
         .child(El::new("br"))
         .child(El::new("button").text("add").classes(&BUTTON_CLASSES).on("click", move |_| {
             let count = signal_count.get() + 1;
             signal_count.set(count);
         }))
-        .child(El::new("div").text("0").once(move |el| {
-            signal_count.on(move |v| { Js::invoke("{}.innerHTML = {}", &[el.into(), v.to_string().into()]); });
-        }))
-        .child(El::new("div").text("-").once(move |el| {
-            signal_time.on(move |v| { Js::invoke("{}.innerHTML = {}", &[el.into(), v.into()]); });
-        }))
-        .child(El::new("div").text("-").once(move |el| {
-            signal_key.on(move |v| { Js::invoke("{}.innerHTML = {}", &[el.into(), v.into()]); });
-        }))
+        .child(El::new("div").text_signal(signal_count))
+        .child(El::new("div").text_signal(signal_time))
+        .child(El::new("div").text_signal(signal_key))
 }
 
 fn page2() -> El {
+    let router = use_context::<RouterContext>().unwrap();
     El::new("div")
         .classes(&["m-2"])
         .child(El::new("button").text("page 1").classes(&BUTTON_CLASSES).on("click", move |_| {
-            ROUTER.with(|s| { s.borrow().navigate("/page1"); });
+            router.borrow().navigate("/page1");
         }))
 }
 
-#[no_mangle]
+pub fn pages() -> Vec<Page> {
+    vec![Page::new("/page1", page1).ttile("Page 1".to_owned()), Page::new("/page2", page2).ttile("Page 2".to_owned())]
+}
+
+// NOTE: only exported in wasm so it doesn't clash with the `main` of the generate binary
+#[cfg_attr(target_arch = "wasm32", no_mangle)]
 pub fn main() {
 
     std::panic::set_hook(Box::new(|e| { Js::invoke("console.log({})", &[e.to_string().into()]); }));
 
-    // init router
-    let pages = &[Page::new("/page1", page1()), Page::new("/page2", page2())];
-    ROUTER.with(|s| { *s.borrow_mut() = Router::new("body", pages); });
+    // apply signal updates once per frame
+    frame::defer_writes(true);
+
+    // init router, pages get it from the context
+    Scope::root().run(|| {
+        let router = RouterContext::default();
+        provide_context(router.clone());
+        let new_router = Router::new("body", &pages());
+        *router.borrow_mut() = new_router;
+    });
+}
+
+#[no_mangle]
+pub fn worker_main() {
+
+    // count primes off the main thread
+    let mut scope = WorkerScope::<String, String>::new();
+    Runtime::block_on(async move {
+        while let Ok(message) = scope.recv().await {
+            let n = message.parse::<u64>().unwrap_or_default();
+            let count = (2..n).filter(|i| (2..*i).take_while(|d| d * d <= *i).all(|d| i % d != 0)).count();
+            scope.post(count.to_string());
+        }
+    });
 }
@@ -4,6 +4,18 @@ let wasmModule = {}
 
 const objects = []
 
+// NOTE: nodes created by `applyCommands` have ids assigned in wasm with the high bit set
+const NODE_BIT = 0x80000000
+const nodes = []
+
+const getObject = (id) => id >= NODE_BIT ? nodes[id - NODE_BIT] : objects[id]
+
+const isWorker = typeof WorkerGlobalScope !== 'undefined' && self instanceof WorkerGlobalScope
+const scriptUrl = typeof document !== 'undefined' ? document.currentScript.src : isWorker ? self.location.href : null
+
+// messages received in a worker before the wasm handler is set
+const workerMessages = []
+
 const textEncoder = new TextEncoder()
 const textDecoder = new TextDecoder()
 
@@ -40,8 +52,13 @@ const readParamsFromMemory = (ptr, len) => {
             i += 1
         } else if (params[i] === 7) { // object ref
             const objectId = dataView.getUint32(i + 1, true)
-            values.push(objects[objectId])
+            values.push(getObject(objectId))
             i += 1 + 4
+        } else if (params[i] === 8) { // buffer
+            const ptr = dataView.getInt32(i + 1, true)
+            const len = dataView.getInt32(i + 1 + 4, true)
+            values.push(memory.slice(ptr, ptr + len))
+            i += 1 + 4 + 4
         } else {
             throw new Error('Invalid parameter type')
         }
@@ -92,13 +109,175 @@ const getWasmImports = () => {
             }
         },
       __deallocate(object_id) {
-          const index = objects.indexOf(object_id)
-          objects.splice(index, 1);
+          // NOTE: object ids are indexes so the array should not be shifted
+          if (object_id >= NODE_BIT) { delete nodes[object_id - NODE_BIT] } else { delete objects[object_id] }
       }
     }
     return { env }
 }
 
+// reads bytes, little endian u32, length prefixed strings and typed values in order
+const createReader = (bytes) => {
+    const dataView = new DataView(bytes.buffer, bytes.byteOffset, bytes.byteLength)
+    const reader = { i: 0 }
+    reader.isDone = () => reader.i >= bytes.length
+    reader.readByte = () => bytes[reader.i++]
+    reader.readU32 = () => { const value = dataView.getUint32(reader.i, true); reader.i += 4; return value }
+    reader.readStr = () => {
+        const len = reader.readU32()
+        const value = textDecoder.decode(bytes.subarray(reader.i, reader.i + len))
+        reader.i += len
+        return value
+    }
+    // NOTE: values have the types of invoke params but strings and buffers are length prefixed
+    reader.readValue = () => {
+        const type = reader.readByte()
+        if (type === 0) return undefined
+        if (type === 1) return null
+        if (type === 2) { const value = dataView.getFloat64(reader.i, true); reader.i += 8; return value }
+        if (type === 3) { const value = dataView.getBigInt64(reader.i, true); reader.i += 8; return value }
+        if (type === 4) return reader.readStr()
+        if (type === 5) return true
+        if (type === 6) return false
+        if (type === 7) return getObject(reader.readU32())
+        if (type === 8) { const len = reader.readU32(); reader.i += len; return bytes.slice(reader.i - len, reader.i) }
+        throw new Error('Invalid value type')
+    }
+    return reader
+}
+
+const setNode = (id, node) => { nodes[id - NODE_BIT] = node }
+
+// applies the dom commands recorded in wasm in a single pass
+// NOTE: released node ids are freed after the pass as later commands of the batch can still use them
+const applyCommands = (bytes) => {
+    const { isDone, readByte, readU32: readRef, readStr, readValue } = createReader(bytes)
+    const released = []
+    while (!isDone()) {
+        const opcode = readByte()
+        if (opcode === 0) { // create element
+            const id = readRef()
+            setNode(id, document.createElement(readStr()))
+        } else if (opcode === 1) { // create text
+            const id = readRef()
+            setNode(id, document.createTextNode(readStr()))
+        } else if (opcode === 2) { // create comment
+            setNode(readRef(), document.createComment(''))
+        } else if (opcode === 3) { // set attribute
+            const node = getObject(readRef())
+            const name = readStr()
+            node.setAttribute(name, readStr())
+        } else if (opcode === 4) { // add class
+            const node = getObject(readRef())
+            node.classList.add(readStr())
+        } else if (opcode === 5) { // append
+            const parent = getObject(readRef())
+            parent.appendChild(getObject(readRef()))
+        } else if (opcode === 6) { // append text
+            const parent = getObject(readRef())
+            parent.appendChild(document.createTextNode(readStr()))
+        } else if (opcode === 7) { // clear
+            getObject(readRef()).innerHTML = ''
+        } else if (opcode === 8) { // listen
+            const node = getObject(readRef())
+            const event = readStr()
+            node.addEventListener(event, getObject(readRef()))
+        } else if (opcode === 9) { // remove attribute
+            const node = getObject(readRef())
+            node.removeAttribute(readStr())
+        } else if (opcode === 10) { // toggle class
+            const node = getObject(readRef())
+            const name = readStr()
+            node.classList.toggle(name, readByte() !== 0)
+        } else if (opcode === 11) { // set style
+            const node = getObject(readRef())
+            const property = readStr()
+            node.style.setProperty(property, readStr())
+        } else if (opcode === 12) { // set text
+            const node = getObject(readRef())
+            node.data = readStr()
+        } else if (opcode === 13) { // insert before
+            const anchor = getObject(readRef())
+            anchor.before(getObject(readRef()))
+        } else if (opcode === 14) { // remove
+            getObject(readRef()).remove()
+        } else if (opcode === 15) { // unlisten
+            const node = getObject(readRef())
+            const event = readStr()
+            node.removeEventListener(event, getObject(readRef()))
+        } else if (opcode === 16) { // release
+            released.push(readRef())
+        } else if (opcode === 17) { // set property
+            const node = getObject(readRef())
+            const name = readStr()
+            node[name] = readValue()
+        } else {
+            throw new Error('Invalid command')
+        }
+    }
+    released.forEach((id) => { delete nodes[id - NODE_BIT] })
+}
+
+// matches the tree of nodes built in wasm with the server rendered dom in `parent` and replaces mismatching nodes
+// NOTE: the comments that separate text nodes are skipped and empty text nodes are created as they aren't rendered
+const hydrate = (parent, bytes, debug) => {
+    const { readByte, readU32, readStr } = createReader(bytes)
+    const mismatch = (message, node) => { if (debug) console.warn(`Hydration mismatch: ${message}`, node) }
+    const isSeparator = (node) => node?.nodeType === Node.COMMENT_NODE && node.data === ','
+    const insert = (parent, node, current) => { parent.insertBefore(node, current ?? null); return node }
+    const replace = (parent, node, current) => { insert(parent, node, current); current?.remove(); return node }
+
+    // returns the dom node after the matched one
+    const match = (parent, current) => {
+        while (isSeparator(current)) current = current.nextSibling
+        const kind = readByte()
+        const id = readU32()
+        let node = current
+        if (kind === 0) { // element
+            const tag = readStr()
+            const attributes = Array.from({ length: readU32() }, () => [readStr(), readStr()])
+            if (node?.nodeType !== Node.ELEMENT_NODE || node.localName !== tag) {
+                mismatch(`expected <${tag}>`, node ?? parent)
+                node = replace(parent, document.createElement(tag), current)
+            }
+            for (const [name, value] of attributes) {
+                const currentValue = node.getAttribute(name)
+                if (currentValue === value || (currentValue === '' && value !== '')) continue // boolean attribute
+                mismatch(`expected ${name}="${value}"`, node)
+                node.setAttribute(name, value)
+            }
+            let child = node.firstChild
+            const count = readU32()
+            for (let j = 0; j < count; j++) child = match(node, child)
+            while (child) {
+                const next = child.nextSibling
+                if (!isSeparator(child)) { mismatch('unexpected node', child); child.remove() }
+                child = next
+            }
+        } else if (kind === 1) { // text
+            const text = readStr()
+            if (node?.nodeType !== Node.TEXT_NODE) {
+                if (text !== '') mismatch(`expected "${text}"`, node ?? parent)
+                node = insert(parent, document.createTextNode(text), current)
+            } else if (node.data !== text && node.data.startsWith(text)) {
+                node.splitText(text.length)
+            } else if (node.data !== text) {
+                mismatch(`expected "${text}"`, node)
+                node.data = text
+            }
+        } else { // comment
+            if (node?.nodeType !== Node.COMMENT_NODE || isSeparator(node)) {
+                mismatch('expected comment', node ?? parent)
+                node = replace(parent, document.createComment(''), current)
+            }
+        }
+        if (id >= NODE_BIT) setNode(id, node) // appended texts have no id
+        return node.nextSibling
+    }
+    if (bytes.length === 0) return
+    match(parent, parent.firstChild)
+}
+
 const loadWasm = async () => {
     const imports = getWasmImports()
     const wasmScript = document.querySelector('script[type="application/wasm"]')
@@ -107,6 +286,31 @@ const loadWasm = async () => {
     wasmModule.instance.exports.main()
 }
 
+// starts the same wasm module in a worker and calls the `entry` export there
+const spawnWorker = (entry) => {
+    const worker = new Worker(scriptUrl)
+    worker.postMessage({ module: wasmModule.module, entry })
+    return worker
+}
+
+const loadWorker = async (e) => {
+    const { module, entry } = e.data
+    self.onmessage = (e) => workerMessages.push(e)
+    const instance = await WebAssembly.instantiate(module, getWasmImports())
+    wasmModule = { module, instance }
+    wasmModule.instance.exports[entry]()
+}
+
+const setWorkerHandler = (handler) => {
+    self.onmessage = handler
+    workerMessages.splice(0).forEach(handler)
+}
+
+// NOTE: buffers are copied out of wasm memory so they can be transferred
+const postMessageTo = (target, data) => {
+    target.postMessage(data, data instanceof Uint8Array ? [data.buffer] : [])
+}
+
 const writeBufferToMemory = (buffer) => {
     const allocationId = wasmModule.instance.exports.create_allocation(buffer.length)
     const allocationPtr = wasmModule.instance.exports.get_allocation(allocationId)
@@ -119,10 +323,15 @@ const loadExports = () => {
     exports.wasmModule = wasmModule
     exports.writeBufferToMemory = writeBufferToMemory
     exports.readParamsFromMemory = readParamsFromMemory
+    exports.applyCommands = applyCommands
+    exports.hydrate = hydrate
+    exports.getObject = getObject
 }
 
 if (typeof window !== 'undefined') { // load wasm (browser)
     document.addEventListener('DOMContentLoaded', loadWasm)
+} else if (isWorker) { // load wasm (worker)
+    self.onmessage = loadWorker
 } else { // load exports (nodejs)
     loadExports()
 }
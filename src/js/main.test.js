@@ -1,7 +1,7 @@
 const test = require('node:test')
 const assert = require('node:assert')
 
-const { readParamsFromMemory, writeBufferToMemory, wasmModule } = require('./main')
+const { readParamsFromMemory, writeBufferToMemory, applyCommands, hydrate, getObject, wasmModule } = require('./main')
 
 // node src/js/main.test.js
 
@@ -28,6 +28,7 @@ test('check read params', () => {
         {memory: [5], expected: [true]},
         {memory: [6], expected: [false]},
         {memory: [7, ...uint32Array], expected: [undefined]},
+        {memory: [8, ...uint32Array, ...uint32Array], expected: [new Uint8Array()]},
     ]
     for (const testCase of testCases) {
         wasmModule.instance = { exports: { memory: { buffer: testCase.memory } } }
@@ -53,3 +54,84 @@ test('check write buffer', () => {
         assert.deepStrictEqual(result, testCase.expected)
     }
 })
+
+test('check apply commands', () => {
+
+    // minimal dom
+    let node = null
+    global.document = {
+        createElement: (tag) => {
+            node = { tag, children: [], attributes: {}, appendChild (c) { this.children.push(c) }, setAttribute (k, v) { this.attributes[k] = v } }
+            return node
+        },
+        createTextNode: (text) => ({ text }),
+    }
+
+    // create div, set id to "a", append text "b" to div
+    const str = (s) => [s.length, 0, 0, 0, ...new TextEncoder().encode(s)]
+    const id = [0, 0, 0, 0x80]
+    const bytes = new Uint8Array([0, ...id, ...str('div'), 3, ...id, ...str('id'), ...str('a'), 6, ...id, ...str('b')])
+    applyCommands(bytes)
+
+    assert.deepStrictEqual(node.tag, 'div')
+    assert.deepStrictEqual(node.attributes, { id: 'a' })
+    assert.deepStrictEqual(node.children, [{ text: 'b' }])
+
+    // set checked to true and value to "c"
+    applyCommands(new Uint8Array([17, ...id, ...str('checked'), 5, 17, ...id, ...str('value'), 4, ...str('c')]))
+    assert.deepStrictEqual([node.checked, node.value], [true, 'c'])
+    delete global.document
+})
+
+test('check hydrate', () => {
+
+    // minimal dom
+    class MockNode {
+        constructor (nodeType, value) { Object.assign(this, { nodeType, localName: value, data: value, parent: null, children: [], attributes: {} }) }
+        get firstChild () { return this.children[0] ?? null }
+        get nextSibling () { const siblings = this.parent?.children ?? []; return siblings[siblings.indexOf(this) + 1] ?? null }
+        getAttribute (name) { return this.attributes[name] ?? null }
+        setAttribute (name, value) { this.attributes[name] = value }
+        insertBefore (node, before) { node.parent = this; this.children.splice(before ? this.children.indexOf(before) : this.children.length, 0, node) }
+        remove () { this.parent.children.splice(this.parent.children.indexOf(this), 1) }
+    }
+    global.Node = { ELEMENT_NODE: 1, TEXT_NODE: 3, COMMENT_NODE: 8 }
+    global.document = { createElement: (tag) => new MockNode(1, tag), createTextNode: (text) => new MockNode(3, text), createComment: (data) => new MockNode(8, data) }
+
+    // server rendered <div><b>a</b><i></i></div>
+    const body = new MockNode(1, 'body')
+    const div = new MockNode(1, 'div')
+    const b = new MockNode(1, 'b')
+    body.insertBefore(div)
+    div.insertBefore(b)
+    b.insertBefore(new MockNode(3, 'a'))
+    div.insertBefore(new MockNode(1, 'i'))
+
+    // expected <div><b>a</b><!----></div>
+    const u32 = (v) => [v & 0xff, (v >> 8) & 0xff, (v >> 16) & 0xff, (v >> 24) & 0xff]
+    const str = (s) => [...u32(s.length), ...new TextEncoder().encode(s)]
+    const id = (i) => u32(0x80000000 + i)
+    const bytes = new Uint8Array([
+        0, ...id(0), ...str('div'), ...u32(0), ...u32(2),
+        0, ...id(1), ...str('b'), ...u32(0), ...u32(1),
+        1, ...u32(1), ...str('a'),
+        2, ...id(3),
+    ])
+    hydrate(body, bytes, false)
+
+    assert.strictEqual(body.children[0], div)
+    assert.strictEqual(div.children[0], b)
+    assert.strictEqual(getObject(0x80000000 + 1), b)
+    assert.strictEqual(getObject(1), undefined)
+    assert.deepStrictEqual(div.children.map(c => c.nodeType), [1, 8])
+
+    // replace mismatching root
+    const section = new MockNode(1, 'section')
+    div.remove()
+    body.insertBefore(section)
+    hydrate(body, bytes, false)
+    assert.deepStrictEqual(body.children.map(c => c.localName), ['div'])
+    hydrate(body, new Uint8Array(), false)
+    delete global.document
+    delete global.Node
+})
@@ -3,16 +3,29 @@ use crate::runtime::{FutureState, FutureTask};
 use crate::invoke::{Js, ObjectRef};
 
 use std::collections::HashMap;
+#[cfg(not(target_arch = "wasm32"))]
+use std::cell::Cell;
 use std::cell::RefCell;
 use std::rc::Rc;
 
+type Handler = Rc<RefCell<dyn FnMut(ObjectRef) + 'static>>;
+
+thread_local! {
+    pub static CALLBACK_HANDLERS: RefCell<HashMap<ObjectRef, Handler>> = Default::default();
+}
+
+// NOTE: outside the browser there are no javascript objects so callbacks are numbered in order
+#[cfg(not(target_arch = "wasm32"))]
 thread_local! {
-    pub static CALLBACK_HANDLERS: RefCell<HashMap<ObjectRef, Box<dyn FnMut(ObjectRef) + 'static>>> = Default::default();
+    static NEXT_CALLBACK: Cell<u32> = const { Cell::new(0) };
 }
 
+// NOTE: callbacks that are deallocated aren't called, eg. a promise that resolves after it's cancelled
 pub fn create_callback(mut handler: impl FnMut(ObjectRef) + 'static) -> ObjectRef {
+    #[cfg(target_arch = "wasm32")]
     let code = r#"
         const handler = (e) => {
+            if (!(objectId in objects)) return;
             objects.push(e);
             const callbackObjectId = objects.length - 1;
             wasmModule.instance.exports.handle_callback(objectId,callbackObjectId);
@@ -21,10 +34,13 @@ pub fn create_callback(mut handler: impl FnMut(ObjectRef) + 'static) -> ObjectRe
         const objectId = objects.length - 1;
         return objectId;
     "#;
-    let object_id = Js::invoke(code, &[]).to_num().unwrap();
-    let function_ref = ObjectRef::new(object_id as u32);
+    #[cfg(target_arch = "wasm32")]
+    let object_id = Js::invoke(code, &[]).to_num().unwrap() as u32;
+    #[cfg(not(target_arch = "wasm32"))]
+    let object_id = NEXT_CALLBACK.replace(NEXT_CALLBACK.get() + 1);
+    let function_ref = ObjectRef::new(object_id);
     let cb = move |value| { handler(value); };
-    CALLBACK_HANDLERS.with(|s| { s.borrow_mut().insert(function_ref.clone(), Box::new(cb)); });
+    CALLBACK_HANDLERS.with(|s| { s.borrow_mut().insert(function_ref.clone(), Rc::new(RefCell::new(cb))); });
     function_ref
 }
 
@@ -33,21 +49,28 @@ pub fn handle_callback(callback_id: u32, param: i32) {
 
     let object_ref = ObjectRef::new(param as u32);
 
-    CALLBACK_HANDLERS.with(|s| {
-        let handler = s.borrow_mut().get_mut(&ObjectRef::new(callback_id)).unwrap() as *mut Box<dyn FnMut(_) + 'static>;
-        unsafe { (*handler)(object_ref) }
-    });
+    // NOTE: the handler is cloned so it can be removed while it runs, eg. when it unmounts its element
+    let handler = CALLBACK_HANDLERS.with(|s| s.borrow().get(&ObjectRef::new(callback_id)).cloned());
+    let handler = handler.unwrap_or_else(|| panic!("Callback {} should exist", callback_id));
+
+    // NOTE: a handler that is called again while it runs, eg. when it dispatches the event it listens to, is skipped
+    if let Ok(mut handler) = handler.try_borrow_mut() { handler(object_ref); }
+
+    // apply dom updates made by the handler
+    crate::commands::flush();
 
     Js::deallocate(object_ref);
 }
 
+// NOTE: the resolved value is retained so the caller owns it and should deallocate it with `Js::deallocate`
+// (breaking, it used to be deallocated when the callback returned so it couldn't be used after `await`)
 pub fn create_async_callback() -> (ObjectRef, FutureTask<ObjectRef>) {
     let future = FutureTask { state: Rc::new(RefCell::new(FutureState::Init)) };
     let state_clone = future.state.clone();
     let callback_ref = create_callback(move |e| {
         let mut future_state = state_clone.borrow_mut();
         if let FutureState::Pending(ref mut waker) = &mut *future_state { waker.to_owned().wake(); }
-        *future_state = FutureState::Ready(e);
+        *future_state = FutureState::Ready(Js::retain(e));
     });
     return (callback_ref, future);
 }
@@ -77,6 +100,22 @@ mod tests {
         CALLBACK_HANDLERS.with(|s| { s.borrow_mut().remove(&function_ref); });
         let count = CALLBACK_HANDLERS.with(|s| s.borrow().len());
         assert_eq!(count, 0);
+
+        // skip reentrant calls
+        let runs = Rc::new(RefCell::new(0));
+        let runs_clone = runs.clone();
+        let function_ref: Rc<RefCell<Option<ObjectRef>>> = Default::default();
+        let function_ref_clone = function_ref.clone();
+        let callback = create_callback(move |_| {
+            *runs_clone.borrow_mut() += 1;
+            handle_callback(*function_ref_clone.borrow().unwrap(), 0);
+        });
+        *function_ref.borrow_mut() = Some(callback);
+        handle_callback(*callback, 0);
+        assert_eq!(*runs.borrow(), 1);
+
+        // panic for unknown callbacks
+        assert!(std::panic::catch_unwind(|| handle_callback(100, 0)).is_err());
     }
 
     #[test]
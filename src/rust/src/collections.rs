@@ -0,0 +1,354 @@
+
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::rc::{Rc, Weak};
+
+use crate::signals::{Signal, Subscription};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VecDiff<T> {
+    Insert { index: usize, value: T },
+    Remove { index: usize },
+    Move { from: usize, to: usize },
+    Update { index: usize, value: T },
+    Clear,
+}
+
+impl<T> VecDiff<T> {
+    pub fn apply(self, values: &mut Vec<T>) {
+        match self {
+            Self::Insert { index, value } => values.insert(index, value),
+            Self::Remove { index } => { values.remove(index); },
+            Self::Move { from, to } => { let value = values.remove(from); values.insert(to, value); },
+            Self::Update { index, value } => values[index] = value,
+            Self::Clear => values.clear(),
+        }
+    }
+}
+
+// changes that turn `old` into `new`
+pub fn diff<T: Clone + PartialEq>(old: &[T], new: &[T]) -> Vec<VecDiff<T>> {
+
+    if new.is_empty() {
+        return if old.is_empty() { vec![] } else { vec![VecDiff::Clear] };
+    }
+
+    // skip common prefix and suffix
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..].iter().rev().zip(new[prefix..].iter().rev()).take_while(|(a, b)| a == b).count();
+    let old = &old[prefix..old.len() - suffix];
+    let new = &new[prefix..new.len() - suffix];
+
+    // NOTE: a single item moved is common when reordering lists so it's detected separately
+    if old.len() == new.len() && old.len() > 1 {
+        let last = old.len() - 1;
+        if old[0] == new[last] && old[1..] == new[..last] {
+            return vec![VecDiff::Move { from: prefix, to: prefix + last }];
+        }
+        if old[last] == new[0] && old[..last] == new[1..] {
+            return vec![VecDiff::Move { from: prefix + last, to: prefix }];
+        }
+    }
+
+    // update items in place then remove or insert the rest
+    let common = old.len().min(new.len());
+    let mut diffs = (0..common)
+        .filter(|i| old[*i] != new[*i])
+        .map(|i| VecDiff::Update { index: prefix + i, value: new[i].clone() })
+        .collect::<Vec<_>>();
+    diffs.extend((common..old.len()).map(|_| VecDiff::Remove { index: prefix + common }));
+    diffs.extend(new[common..].iter().enumerate().map(|(i, v)| VecDiff::Insert { index: prefix + common + i, value: v.clone() }));
+    diffs
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MapDiff<K, V> {
+    Insert { key: K, value: V },
+    Remove { key: K },
+    Update { key: K, value: V },
+    Clear,
+}
+
+type Queue<D> = Rc<RefCell<Vec<D>>>;
+
+// NOTE: each subscriber has its own queue so diffs made in a batch are delivered together
+struct Inner<S, D> { value: S, queues: RefCell<Vec<Weak<RefCell<Vec<D>>>>> }
+
+impl<S, D: Clone> Inner<S, D> {
+    fn emit(&self, diffs: &[D]) {
+        let mut queues = self.queues.borrow_mut();
+        queues.retain(|q| q.strong_count() > 0);
+        queues.iter().filter_map(Weak::upgrade).for_each(|q| q.borrow_mut().extend_from_slice(diffs));
+    }
+}
+
+// runs `cb` with the diffs since its last run, starting with `initial`
+fn listen<S: 'static, D: Clone + 'static>(signal: Signal<Inner<S, D>>, initial: impl FnOnce(&S) -> Vec<D>, mut cb: impl FnMut(&[D]) + 'static) -> Subscription {
+    let queue: Queue<D> = Rc::new(RefCell::new(vec![]));
+    signal.with_untracked(|s| {
+        *queue.borrow_mut() = initial(&s.value);
+        s.queues.borrow_mut().push(Rc::downgrade(&queue));
+    });
+    signal.subscribe(move || {
+        let diffs = queue.take();
+        if !diffs.is_empty() { cb(&diffs); }
+    })
+}
+
+// list that notifies subscribers with the changes made instead of the whole value
+pub struct SignalVec<T: 'static> { signal: Signal<Inner<Vec<T>, VecDiff<T>>> }
+
+impl<T> Clone for SignalVec<T> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<T> Copy for SignalVec<T> {}
+
+impl<T> std::fmt::Debug for SignalVec<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SignalVec").field(&self.signal).finish()
+    }
+}
+
+impl<T: Clone + 'static> SignalVec<T> {
+    pub fn new(values: Vec<T>) -> Self {
+        Self { signal: Signal::new(Inner { value: values, queues: Default::default() }) }
+    }
+    fn apply(&self, diffs: Vec<VecDiff<T>>) {
+        if diffs.is_empty() { return; }
+        self.signal.update(|s| {
+            s.emit(&diffs);
+            diffs.into_iter().for_each(|d| d.apply(&mut s.value));
+        });
+    }
+    pub fn push(&self, value: T) {
+        let index = self.signal.with_untracked(|s| s.value.len());
+        self.apply(vec![VecDiff::Insert { index, value }]);
+    }
+    pub fn insert(&self, index: usize, value: T) {
+        self.apply(vec![VecDiff::Insert { index, value }]);
+    }
+    pub fn remove(&self, index: usize) -> T {
+        let value = self.signal.with_untracked(|s| s.value[index].clone());
+        self.apply(vec![VecDiff::Remove { index }]);
+        value
+    }
+    pub fn move_item(&self, from: usize, to: usize) {
+        if from == to { return; }
+        self.apply(vec![VecDiff::Move { from, to }]);
+    }
+    pub fn set(&self, index: usize, value: T) {
+        self.apply(vec![VecDiff::Update { index, value }]);
+    }
+    pub fn clear(&self) {
+        if self.signal.with_untracked(|s| s.value.is_empty()) { return; }
+        self.apply(vec![VecDiff::Clear]);
+    }
+    pub fn with<R>(&self, f: impl FnOnce(&[T]) -> R) -> R {
+        self.signal.with(|s| f(&s.value))
+    }
+    pub fn get(&self) -> Vec<T> {
+        self.with(|v| v.to_vec())
+    }
+    pub fn len(&self) -> usize {
+        self.signal.with(|s| s.value.len())
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    // runs `cb` with an insert for each item and then with the changes made since its last run
+    pub fn on_diff(&self, cb: impl FnMut(&[VecDiff<T>]) + 'static) -> Subscription {
+        let initial = |v: &Vec<T>| v.iter().cloned().enumerate().map(|(index, value)| VecDiff::Insert { index, value }).collect();
+        listen(self.signal, initial, cb)
+    }
+    pub fn dispose(&self) {
+        self.signal.dispose();
+    }
+    pub fn is_disposed(&self) -> bool {
+        self.signal.is_disposed()
+    }
+}
+
+impl<T: Clone + PartialEq + 'static> SignalVec<T> {
+    // replaces the items emitting only the changes
+    pub fn replace(&self, values: Vec<T>) {
+        let diffs = self.signal.with_untracked(|s| diff(&s.value, &values));
+        self.apply(diffs);
+    }
+}
+
+// follows `signal` and emits the changes between its values
+impl<T: Clone + PartialEq + 'static> From<Signal<Vec<T>>> for SignalVec<T> {
+    fn from(signal: Signal<Vec<T>>) -> Self {
+        let signal_vec = Self::new(signal.with_untracked(Vec::clone));
+        signal.subscribe(move || {
+            let values = signal.with_untracked(Vec::clone);
+            if !signal_vec.is_disposed() { signal_vec.replace(values); }
+        }).detach();
+        signal_vec
+    }
+}
+
+// map that notifies subscribers with the changes made instead of the whole value
+pub struct SignalMap<K: 'static, V: 'static> { signal: Signal<Inner<BTreeMap<K, V>, MapDiff<K, V>>> }
+
+impl<K, V> Clone for SignalMap<K, V> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<K, V> Copy for SignalMap<K, V> {}
+
+impl<K, V> std::fmt::Debug for SignalMap<K, V> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SignalMap").field(&self.signal).finish()
+    }
+}
+
+impl<K: Ord + Clone + 'static, V: Clone + 'static> Default for SignalMap<K, V> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<K: Ord + Clone + 'static, V: Clone + 'static> SignalMap<K, V> {
+    pub fn new() -> Self {
+        Self { signal: Signal::new(Inner { value: BTreeMap::new(), queues: Default::default() }) }
+    }
+    fn apply(&self, diff: MapDiff<K, V>) {
+        self.signal.update(|s| {
+            s.emit(std::slice::from_ref(&diff));
+            match diff {
+                MapDiff::Insert { key, value } | MapDiff::Update { key, value } => { s.value.insert(key, value); },
+                MapDiff::Remove { key } => { s.value.remove(&key); },
+                MapDiff::Clear => s.value.clear(),
+            }
+        });
+    }
+    pub fn insert(&self, key: K, value: V) {
+        match self.signal.with_untracked(|s| s.value.contains_key(&key)) {
+            true => self.apply(MapDiff::Update { key, value }),
+            false => self.apply(MapDiff::Insert { key, value }),
+        }
+    }
+    pub fn remove(&self, key: &K) -> Option<V> {
+        let value = self.signal.with_untracked(|s| s.value.get(key).cloned())?;
+        self.apply(MapDiff::Remove { key: key.clone() });
+        Some(value)
+    }
+    pub fn clear(&self) {
+        if self.signal.with_untracked(|s| s.value.is_empty()) { return; }
+        self.apply(MapDiff::Clear);
+    }
+    pub fn get(&self, key: &K) -> Option<V> {
+        self.signal.with(|s| s.value.get(key).cloned())
+    }
+    pub fn contains_key(&self, key: &K) -> bool {
+        self.signal.with(|s| s.value.contains_key(key))
+    }
+    pub fn with<R>(&self, f: impl FnOnce(&BTreeMap<K, V>) -> R) -> R {
+        self.signal.with(|s| f(&s.value))
+    }
+    pub fn len(&self) -> usize {
+        self.signal.with(|s| s.value.len())
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    // runs `cb` with an insert for each entry and then with the changes made since its last run
+    pub fn on_diff(&self, cb: impl FnMut(&[MapDiff<K, V>]) + 'static) -> Subscription {
+        let initial = |m: &BTreeMap<K, V>| m.iter().map(|(k, v)| MapDiff::Insert { key: k.clone(), value: v.clone() }).collect();
+        listen(self.signal, initial, cb)
+    }
+    pub fn dispose(&self) {
+        self.signal.dispose();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::signals::{batch, create_effect, Memo};
+
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+
+        let cases: &[(&[u32], &[u32])] = &[
+            (&[1, 2, 3], &[1, 3]),
+            (&[1, 2, 3], &[0, 1, 2, 3, 4]),
+            (&[1, 2, 3, 4], &[2, 3, 4, 1]),
+            (&[1, 2, 3, 4], &[4, 1, 2, 3]),
+            (&[1, 2, 3], &[5, 6]),
+            (&[1, 2], &[]),
+        ];
+        for (old, new) in cases {
+            let mut values = old.to_vec();
+            diff(old, new).into_iter().for_each(|d| d.apply(&mut values));
+            assert_eq!(&values, new);
+        }
+        assert_eq!(diff(&[1, 2, 3], &[2, 3, 1]), vec![VecDiff::Move { from: 0, to: 2 }]);
+        assert_eq!(diff(&[1, 2, 3], &[1, 5, 3]), vec![VecDiff::Update { index: 1, value: 5 }]);
+    }
+
+    #[test]
+    fn test_signal_vec() {
+
+        // initial items
+        let logs: Rc<RefCell<Vec<Vec<VecDiff<u32>>>>> = Default::default();
+        let logs_clone = logs.clone();
+        let values = SignalVec::new(vec![1]);
+        let _subscription = values.on_diff(move |d| logs_clone.borrow_mut().push(d.to_vec()));
+        assert_eq!(logs.borrow()[0], vec![VecDiff::Insert { index: 0, value: 1 }]);
+
+        // deliver batched changes together
+        batch(|| {
+            values.push(2);
+            values.move_item(1, 0);
+            values.remove(1);
+        });
+        assert_eq!(logs.borrow().len(), 2);
+        assert_eq!(values.get(), vec![2]);
+
+        // diff plain signal
+        let signal = Signal::new(vec![1, 2, 3]);
+        let values = SignalVec::from(signal);
+        let logs_clone = logs.clone();
+        let _subscription = values.on_diff(move |d| logs_clone.borrow_mut().push(d.to_vec()));
+        signal.set(vec![1, 3]);
+        assert_eq!(logs.borrow().last().unwrap(), &vec![VecDiff::Remove { index: 1 }]);
+        assert_eq!(values.get(), vec![1, 3]);
+
+        // track length
+        let is_empty = Memo::new(move || values.is_empty());
+        assert_eq!(is_empty.get(), false);
+        values.clear();
+        assert_eq!(is_empty.get(), true);
+    }
+
+    #[test]
+    fn test_signal_map() {
+
+        let logs: Rc<RefCell<Vec<MapDiff<&str, u32>>>> = Default::default();
+        let logs_clone = logs.clone();
+        let map = SignalMap::new();
+        let _subscription = map.on_diff(move |d| logs_clone.borrow_mut().extend_from_slice(d));
+        map.insert("a", 1);
+        map.insert("a", 2);
+        assert_eq!(map.remove(&"a"), Some(2));
+        assert_eq!(map.remove(&"a"), None);
+        assert_eq!(*logs.borrow(), vec![
+            MapDiff::Insert { key: "a", value: 1 },
+            MapDiff::Update { key: "a", value: 2 },
+            MapDiff::Remove { key: "a" },
+        ]);
+
+        // track getters
+        let sizes: Rc<RefCell<Vec<(usize, bool)>>> = Default::default();
+        let sizes_clone = sizes.clone();
+        create_effect(move || sizes_clone.borrow_mut().push((map.len(), map.contains_key(&"b"))));
+        map.insert("b", 1);
+        assert_eq!(*sizes.borrow(), vec![(0, false), (1, true)]);
+    }
+
+}
@@ -0,0 +1,263 @@
+
+use std::cell::{Cell, RefCell};
+
+#[cfg(target_arch = "wasm32")]
+use crate::invoke::Js;
+use crate::invoke::{JsValue, ObjectRef};
+
+// NOTE: node ids are assigned in Rust and marked with the high bit so they are looked up in
+// the `nodes` array in Javascript instead of `objects`
+pub const NODE_BIT: u32 = 1 << 31;
+
+thread_local! {
+    static NEXT_NODE: Cell<u32> = const { Cell::new(0) };
+    static COMMANDS: RefCell<Vec<u8>> = const { RefCell::new(vec![]) };
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Command {
+    Create { node: ObjectRef, tag: String },
+    CreateText { node: ObjectRef, text: String },
+    CreateComment { node: ObjectRef },
+    SetAttribute { node: ObjectRef, name: String, value: String },
+    AddClass { node: ObjectRef, name: String },
+    Append { parent: ObjectRef, child: ObjectRef },
+    AppendText { parent: ObjectRef, text: String },
+    Clear { node: ObjectRef },
+    Listen { node: ObjectRef, event: String, callback: ObjectRef },
+    RemoveAttribute { node: ObjectRef, name: String },
+    ToggleClass { node: ObjectRef, name: String, enabled: bool },
+    SetStyle { node: ObjectRef, property: String, value: String },
+    SetText { node: ObjectRef, text: String },
+    InsertBefore { anchor: ObjectRef, node: ObjectRef },
+    Remove { node: ObjectRef },
+    Unlisten { node: ObjectRef, event: String, callback: ObjectRef },
+    Release { node: ObjectRef },
+    SetProperty { node: ObjectRef, name: String, value: JsValue },
+}
+
+pub(crate) fn write_ref(buffer: &mut Vec<u8>, object_ref: ObjectRef) {
+    buffer.extend_from_slice(&object_ref.to_le_bytes());
+}
+
+fn write_bytes(buffer: &mut Vec<u8>, bytes: &[u8]) {
+    buffer.extend_from_slice(&(bytes.len() as u32).to_le_bytes());
+    buffer.extend_from_slice(bytes);
+}
+
+pub(crate) fn write_str(buffer: &mut Vec<u8>, s: &str) {
+    write_bytes(buffer, s.as_bytes());
+}
+
+// NOTE: values use the types of invoke params but strings and buffers are length prefixed instead of pointers
+fn write_value(buffer: &mut Vec<u8>, value: &JsValue) {
+    match value {
+        JsValue::Undefined => buffer.push(0),
+        JsValue::Null => buffer.push(1),
+        JsValue::Number(n) => { buffer.push(2); buffer.extend_from_slice(&n.to_le_bytes()); },
+        JsValue::BigInt(n) => { buffer.push(3); buffer.extend_from_slice(&n.to_le_bytes()); },
+        JsValue::Str(s) => { buffer.push(4); write_str(buffer, s); },
+        JsValue::Bool(b) => buffer.push(if *b { 5 } else { 6 }),
+        JsValue::Ref(r) => { buffer.push(7); write_ref(buffer, *r); },
+        JsValue::Buffer(b) => { buffer.push(8); write_bytes(buffer, b); },
+    }
+}
+
+fn read_bool(bytes: &[u8], i: &mut usize) -> Result<bool, String> {
+    let value = *bytes.get(*i).ok_or("Unexpected end".to_string())?;
+    *i += 1;
+    Ok(value != 0)
+}
+
+fn read_u32(bytes: &[u8], i: &mut usize) -> Result<u32, String> {
+    let value = bytes.get(*i..*i + 4).ok_or("Unexpected end".to_string())?;
+    *i += 4;
+    Ok(u32::from_le_bytes(value.try_into().unwrap()))
+}
+
+fn read_u64(bytes: &[u8], i: &mut usize) -> Result<u64, String> {
+    let value = bytes.get(*i..*i + 8).ok_or("Unexpected end".to_string())?;
+    *i += 8;
+    Ok(u64::from_le_bytes(value.try_into().unwrap()))
+}
+
+fn read_ref(bytes: &[u8], i: &mut usize) -> Result<ObjectRef, String> {
+    read_u32(bytes, i).map(ObjectRef::new)
+}
+
+fn read_bytes(bytes: &[u8], i: &mut usize) -> Result<Vec<u8>, String> {
+    let len = read_u32(bytes, i)? as usize;
+    let value = bytes.get(*i..*i + len).ok_or("Unexpected end".to_string())?;
+    *i += len;
+    Ok(value.to_vec())
+}
+
+fn read_str(bytes: &[u8], i: &mut usize) -> Result<String, String> {
+    String::from_utf8(read_bytes(bytes, i)?).map_err(|_| "Invalid string".to_string())
+}
+
+fn read_value(bytes: &[u8], i: &mut usize) -> Result<JsValue, String> {
+    let kind = *bytes.get(*i).ok_or("Unexpected end".to_string())?;
+    *i += 1;
+    let value = match kind {
+        0 => JsValue::Undefined,
+        1 => JsValue::Null,
+        2 => JsValue::Number(f64::from_bits(read_u64(bytes, i)?)),
+        3 => JsValue::BigInt(read_u64(bytes, i)? as i64),
+        4 => JsValue::Str(read_str(bytes, i)?),
+        5 => JsValue::Bool(true),
+        6 => JsValue::Bool(false),
+        7 => JsValue::Ref(read_ref(bytes, i)?),
+        8 => JsValue::Buffer(read_bytes(bytes, i)?),
+        _ => return Err("Invalid value".to_string()),
+    };
+    Ok(value)
+}
+
+// opcode followed by little endian u32 refs, length prefixed strings, bytes for booleans and typed values
+impl Command {
+    pub fn encode(&self, buffer: &mut Vec<u8>) {
+        match self {
+            Self::Create { node, tag } => { buffer.push(0); write_ref(buffer, *node); write_str(buffer, tag); },
+            Self::CreateText { node, text } => { buffer.push(1); write_ref(buffer, *node); write_str(buffer, text); },
+            Self::CreateComment { node } => { buffer.push(2); write_ref(buffer, *node); },
+            Self::SetAttribute { node, name, value } => { buffer.push(3); write_ref(buffer, *node); write_str(buffer, name); write_str(buffer, value); },
+            Self::AddClass { node, name } => { buffer.push(4); write_ref(buffer, *node); write_str(buffer, name); },
+            Self::Append { parent, child } => { buffer.push(5); write_ref(buffer, *parent); write_ref(buffer, *child); },
+            Self::AppendText { parent, text } => { buffer.push(6); write_ref(buffer, *parent); write_str(buffer, text); },
+            Self::Clear { node } => { buffer.push(7); write_ref(buffer, *node); },
+            Self::Listen { node, event, callback } => { buffer.push(8); write_ref(buffer, *node); write_str(buffer, event); write_ref(buffer, *callback); },
+            Self::RemoveAttribute { node, name } => { buffer.push(9); write_ref(buffer, *node); write_str(buffer, name); },
+            Self::ToggleClass { node, name, enabled } => { buffer.push(10); write_ref(buffer, *node); write_str(buffer, name); buffer.push(*enabled as u8); },
+            Self::SetStyle { node, property, value } => { buffer.push(11); write_ref(buffer, *node); write_str(buffer, property); write_str(buffer, value); },
+            Self::SetText { node, text } => { buffer.push(12); write_ref(buffer, *node); write_str(buffer, text); },
+            Self::InsertBefore { anchor, node } => { buffer.push(13); write_ref(buffer, *anchor); write_ref(buffer, *node); },
+            Self::Remove { node } => { buffer.push(14); write_ref(buffer, *node); },
+            Self::Unlisten { node, event, callback } => { buffer.push(15); write_ref(buffer, *node); write_str(buffer, event); write_ref(buffer, *callback); },
+            Self::Release { node } => { buffer.push(16); write_ref(buffer, *node); },
+            Self::SetProperty { node, name, value } => { buffer.push(17); write_ref(buffer, *node); write_str(buffer, name); write_value(buffer, value); },
+        }
+    }
+    // node that is created or changed
+    pub fn target(&self) -> ObjectRef {
+        match self {
+            Self::Create { node, .. } | Self::CreateText { node, .. } | Self::CreateComment { node } => *node,
+            Self::SetAttribute { node, .. } | Self::AddClass { node, .. } | Self::Clear { node } | Self::Listen { node, .. } => *node,
+            Self::RemoveAttribute { node, .. } | Self::ToggleClass { node, .. } | Self::SetStyle { node, .. } => *node,
+            Self::SetText { node, .. } | Self::InsertBefore { node, .. } | Self::Remove { node } => *node,
+            Self::Unlisten { node, .. } | Self::Release { node } | Self::SetProperty { node, .. } => *node,
+            Self::Append { child, .. } => *child,
+            Self::AppendText { parent, .. } => *parent,
+        }
+    }
+    // nodes that are used by the command, including the target
+    pub fn nodes(&self) -> Vec<ObjectRef> {
+        match self {
+            Self::Append { parent, child } => vec![*parent, *child],
+            Self::InsertBefore { anchor, node } => vec![*anchor, *node],
+            _ => vec![self.target()],
+        }
+    }
+    pub fn decode(bytes: &[u8]) -> Result<Vec<Self>, String> {
+        let (mut commands, mut i) = (vec![], 0);
+        while i < bytes.len() {
+            let opcode = bytes[i];
+            i += 1;
+            let i = &mut i;
+            let command = match opcode {
+                0 => Self::Create { node: read_ref(bytes, i)?, tag: read_str(bytes, i)? },
+                1 => Self::CreateText { node: read_ref(bytes, i)?, text: read_str(bytes, i)? },
+                2 => Self::CreateComment { node: read_ref(bytes, i)? },
+                3 => Self::SetAttribute { node: read_ref(bytes, i)?, name: read_str(bytes, i)?, value: read_str(bytes, i)? },
+                4 => Self::AddClass { node: read_ref(bytes, i)?, name: read_str(bytes, i)? },
+                5 => Self::Append { parent: read_ref(bytes, i)?, child: read_ref(bytes, i)? },
+                6 => Self::AppendText { parent: read_ref(bytes, i)?, text: read_str(bytes, i)? },
+                7 => Self::Clear { node: read_ref(bytes, i)? },
+                8 => Self::Listen { node: read_ref(bytes, i)?, event: read_str(bytes, i)?, callback: read_ref(bytes, i)? },
+                9 => Self::RemoveAttribute { node: read_ref(bytes, i)?, name: read_str(bytes, i)? },
+                10 => Self::ToggleClass { node: read_ref(bytes, i)?, name: read_str(bytes, i)?, enabled: read_bool(bytes, i)? },
+                11 => Self::SetStyle { node: read_ref(bytes, i)?, property: read_str(bytes, i)?, value: read_str(bytes, i)? },
+                12 => Self::SetText { node: read_ref(bytes, i)?, text: read_str(bytes, i)? },
+                13 => Self::InsertBefore { anchor: read_ref(bytes, i)?, node: read_ref(bytes, i)? },
+                14 => Self::Remove { node: read_ref(bytes, i)? },
+                15 => Self::Unlisten { node: read_ref(bytes, i)?, event: read_str(bytes, i)?, callback: read_ref(bytes, i)? },
+                16 => Self::Release { node: read_ref(bytes, i)? },
+                17 => Self::SetProperty { node: read_ref(bytes, i)?, name: read_str(bytes, i)?, value: read_value(bytes, i)? },
+                _ => return Err("Invalid opcode".to_string()),
+            };
+            commands.push(command);
+        }
+        Ok(commands)
+    }
+}
+
+// assigns the id of a node that is created when the commands are applied
+pub fn create_node() -> ObjectRef {
+    let id = NEXT_NODE.get();
+    NEXT_NODE.set(id + 1);
+    ObjectRef::new(NODE_BIT | id)
+}
+
+pub fn is_node(object_ref: ObjectRef) -> bool {
+    *object_ref & NODE_BIT != 0
+}
+
+pub fn push(command: Command) {
+    COMMANDS.with_borrow_mut(|s| command.encode(s));
+}
+
+pub fn is_empty() -> bool {
+    COMMANDS.with_borrow(|s| s.is_empty())
+}
+
+// removes the pending commands without applying them
+pub fn take() -> Vec<u8> {
+    COMMANDS.take()
+}
+
+// applies pending commands in a single call
+// NOTE: called before invokes that use a node and after every callback so nodes exist when they're used
+#[cfg(target_arch = "wasm32")]
+pub fn flush() {
+    let commands = take();
+    if commands.is_empty() { return; }
+    Js::invoke("applyCommands({})", &[JsValue::Buffer(commands)]);
+}
+
+// NOTE: outside the browser commands build the in-memory tree used to render html
+#[cfg(not(target_arch = "wasm32"))]
+pub fn flush() {
+    let commands = take();
+    if commands.is_empty() { return; }
+    crate::html::apply(&Command::decode(&commands).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn test_commands() {
+
+        // encode and decode
+        let (node, body) = (create_node(), ObjectRef::new(1));
+        assert_eq!(is_node(node), true);
+        assert_eq!(is_node(body), false);
+        let commands = vec![
+            Command::Create { node, tag: "div".to_owned() },
+            Command::SetAttribute { node, name: "id".to_owned(), value: "ü".to_owned() },
+            Command::Append { parent: body, child: node },
+            Command::ToggleClass { node, name: "active".to_owned(), enabled: true },
+            Command::Remove { node },
+            Command::Release { node },
+            Command::SetProperty { node, name: "value".to_owned(), value: "a".into() },
+            Command::SetProperty { node, name: "checked".to_owned(), value: false.into() },
+            Command::SetProperty { node, name: "valueAsNumber".to_owned(), value: 1.5.into() },
+        ];
+        commands.iter().for_each(|c| push(c.clone()));
+        assert_eq!(Command::decode(&take()), Ok(commands));
+        assert!(Command::decode(&[0, 1]).is_err());
+    }
+
+}
@@ -1,125 +1,654 @@
 
+use std::collections::HashMap;
 use std::future::Future;
 use std::cell::RefCell;
+use std::hash::Hash;
+use std::pin::Pin;
 use std::rc::Rc;
+use std::task::{Context, Poll};
 
-use crate::invoke::{Js, ObjectRef};
-use crate::runtime::Runtime;
+use crate::commands::{self, Command};
+use crate::events::EventStream;
+use crate::frame;
+use crate::invoke::{Js, JsValue, ObjectRef};
+use crate::runtime::{CancellationToken, Runtime};
+use crate::signals::{create_effect, on_cleanup, untrack, Memo, Scope, Signal, Subscription};
 
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct El { pub element: ObjectRef, pub callbacks: RefCell<Vec<ObjectRef>> }
+// NOTE: signals, subscriptions and child elements created for an element are owned by its scope
+#[derive(Debug, Clone)]
+pub struct El { pub element: ObjectRef, pub callbacks: RefCell<Vec<ObjectRef>>, pub token: CancellationToken, pub scope: Scope }
+
+impl PartialEq for El {
+    fn eq(&self, other: &Self) -> bool {
+        self.element == other.element && self.callbacks == other.callbacks
+    }
+}
+
+impl Eq for El {}
 
 impl El {
     pub fn new(tag: &str) -> Self {
-        let el = Js::invoke("return document.createElement({})", &[tag.into()]).to_ref().unwrap();
-        Self { element: el, callbacks: RefCell::new(vec![]) }
+        let el = commands::create_node();
+        commands::push(Command::Create { node: el, tag: tag.to_owned() });
+        let element = Self::from(&el);
+        element.release_on_cleanup(el);
+        element
+    }
+    // frees the node id in javascript when the element is unmounted
+    fn release_on_cleanup(&self, node: ObjectRef) {
+        self.scope.on_cleanup(move || {
+            frame::discard(node);
+            commands::push(Command::Release { node });
+        });
     }
     pub fn from(el: &ObjectRef) -> Self {
-        Self { element: el.to_owned(), callbacks: RefCell::new(vec![]) }
+        let (token, scope) = (CancellationToken::new(), Scope::new());
+        let token_clone = token.clone();
+        scope.on_cleanup(move || token_clone.cancel());
+        Self { element: el.to_owned(), callbacks: RefCell::new(vec![]), token, scope }
     }
     pub fn mount(&self, parent: &ObjectRef) {
         Js::invoke("{}.appendChild({})", &[parent.into(), self.element.into()]);
     }
+    // reuses the dom rendered by `to_html` in `parent` instead of creating the element and attaches
+    // listeners and bindings to it, mismatching nodes are replaced and reported in debug builds
+    // NOTE: elements used in invokes before hydrating, eg. in `once`, are already created so the content
+    // of `parent` is replaced when it's this element and child elements are appended again otherwise
+    pub fn hydrate(&self, parent: &ObjectRef) -> bool {
+        let commands = Command::decode(&commands::take()).unwrap();
+        let (tree, before, after) = crate::html::hydration(commands, self.element);
+
+        // NOTE: commands of other elements are applied first so only this tree is matched
+        before.into_iter().for_each(commands::push);
+        commands::flush();
+        let is_hydrated = !tree.is_empty();
+        if is_hydrated { Js::invoke("hydrate({},{},{})", &[parent.into(), JsValue::Buffer(tree), cfg!(debug_assertions).into()]); }
+        after.into_iter().for_each(commands::push);
+
+        // mount instead
+        if !is_hydrated {
+            Js::invoke("{}.replaceChildren()", &[parent.into()]);
+            self.mount(parent);
+        }
+        commands::flush();
+        is_hydrated
+    }
+    // renders the element and its children, eg. for the first paint before the wasm module loads
+    // NOTE: outside the browser elements are built in memory instead of the dom
+    #[cfg(not(target_arch = "wasm32"))]
+    pub fn to_html(&self) -> String {
+        commands::flush();
+        crate::html::to_html(self.element)
+    }
     pub fn unmount(&self) {
-        let mut c = self.callbacks.borrow_mut();
-        c.iter().for_each(|p| {
-            crate::callbacks::CALLBACK_HANDLERS.with(|s| { let _ = s.borrow_mut().remove(p).unwrap(); });
-        });
-        c.clear();
+        // NOTE: callbacks are removed when the scope is disposed
+        self.callbacks.borrow_mut().clear();
+
+        // dispose signals, callbacks and tasks
+        self.scope.dispose();
     }
     pub fn attr(self, name: &str, value: &str) -> Self {
-        Js::invoke("{}.setAttribute({},{})", &[self.element.into(), name.into(), value.into()]);
+        commands::push(Command::SetAttribute { node: self.element, name: name.to_owned(), value: value.to_owned() });
         self
     }
+    // runs `f` when the signals it reads change until the element is unmounted
+    fn bind(&self, f: impl FnMut() + 'static) {
+        self.scope.run(|| frame::immediate(|| create_effect(f)));
+    }
+    // sets the attribute while `cb` returns true, `cb` reruns when the signals it reads change
     pub fn attr_fn(self, name: &str, value: &str, cb: impl Fn() -> bool + 'static) -> Self {
-        if cb() {
-            Js::invoke("{}.setAttribute({},{})", &[self.element.into(), name.into(), value.into()]);
-        }
+        let (element, name, value) = (self.element, name.to_owned(), value.to_owned());
+        self.bind(move || match cb() {
+            true => frame::update(Command::SetAttribute { node: element, name: name.to_owned(), value: value.to_owned() }),
+            false => frame::update(Command::RemoveAttribute { node: element, name: name.to_owned() }),
+        });
+        self
+    }
+    pub fn attr_signal<T: ToString + 'static>(self, name: &str, signal: Signal<T>) -> Self {
+        let (element, name) = (self.element, name.to_owned());
+        self.bind(move || {
+            let value = signal.with(|v| v.to_string());
+            frame::update(Command::SetAttribute { node: element, name: name.to_owned(), value });
+        });
+        self
+    }
+    pub fn class_when(self, name: &str, signal: Signal<bool>) -> Self {
+        let (element, name) = (self.element, name.to_owned());
+        self.bind(move || {
+            frame::update(Command::ToggleClass { node: element, name: name.to_owned(), enabled: signal.get() });
+        });
+        self
+    }
+    pub fn style_signal<T: ToString + 'static>(self, property: &str, signal: Signal<T>) -> Self {
+        let (element, property) = (self.element, property.to_owned());
+        self.bind(move || {
+            let value = signal.with(|v| v.to_string());
+            frame::update(Command::SetStyle { node: element, property: property.to_owned(), value });
+        });
+        self
+    }
+    // sets a node property, eg. `value` or `checked` of an input
+    pub fn prop_signal<T: Clone + Into<JsValue> + 'static>(self, name: &str, signal: Signal<T>) -> Self {
+        let (element, name) = (self.element, name.to_owned());
+        self.bind(move || frame::update(Command::SetProperty { node: element, name: name.to_owned(), value: signal.get().into() }));
         self
     }
     pub fn classes(self, classes: &[&str]) -> Self {
-        classes.iter().for_each(|&c| { Js::invoke("{}.classList.add({})", &[self.element.into(), c.into()]); });
+        classes.iter().for_each(|&c| commands::push(Command::AddClass { node: self.element, name: c.to_owned() }));
         self
     }
     pub fn child(self, child: Self) -> Self {
-        Js::invoke("{}.appendChild({})", &[self.element.into(), child.element.into()]);
+        commands::push(Command::Append { parent: self.element, child: child.element });
+        self.scope.adopt(child.scope);
         self
     }
     pub fn children(self, children: &[Self]) -> Self {
-        Js::invoke("{}.innerHTML = {}", &[self.element.into(), "".into()]);
+        commands::push(Command::Clear { node: self.element });
         for child in children {
-            Js::invoke("{}.appendChild({})", &[self.element.into(), child.element.into()]);
+            commands::push(Command::Append { parent: self.element, child: child.element });
+            self.scope.adopt(child.scope);
         }
         self
     }
+    // comment node that marks where elements are inserted
+    fn anchor(&self) -> ObjectRef {
+        let anchor = commands::create_node();
+        commands::push(Command::CreateComment { node: anchor });
+        commands::push(Command::Append { parent: self.element, child: anchor });
+        self.release_on_cleanup(anchor);
+        anchor
+    }
+    // renders the element for the value of `key` in place and replaces it only when the value changes
+    // NOTE: the element is owned by the effect so it's disposed when replaced
+    fn branch<K: Clone + PartialEq + 'static>(&self, key: impl Fn() -> K + 'static, render: impl Fn(K) -> Option<El> + 'static) {
+        let anchor = self.anchor();
+        self.scope.run(|| {
+            let key = Memo::new(key);
+            create_effect(move || {
+                let key = key.get();
+                let Some(el) = untrack(|| render(key)) else { return; };
+                commands::push(Command::InsertBefore { anchor, node: el.element });
+                on_cleanup(move || commands::push(Command::Remove { node: el.element }));
+            });
+        });
+    }
+    // renders `render` while the signal is true and `fallback` otherwise
+    pub fn show(self, when: Signal<bool>, render: impl Fn() -> El + 'static, fallback: impl Fn() -> El + 'static) -> Self {
+        self.branch(move || when.get(), move |v| Some(if v { render() } else { fallback() }));
+        self
+    }
+    // renders the first case that matches the signal value
+    pub fn switch<T: 'static>(self, switch: Switch<T>) -> Self {
+        let Switch { signal, cases, fallback } = switch;
+        let cases = Rc::new(cases);
+        let cases_clone = cases.clone();
+        let key = move || signal.with(|v| cases_clone.iter().position(|c| (c.when)(v)));
+        self.branch(key, move |i| match i {
+            Some(i) => Some((cases[i].render)()),
+            None => fallback.as_ref().map(|f| f()),
+        });
+        self
+    }
+    // renders an element for each item and when the list changes creates, removes and moves only
+    // the elements whose keys changed, each item is rendered in a scope that is disposed on removal
+    pub fn each<T: 'static, K: Eq + Hash + Clone + 'static>(self, list: Signal<Vec<T>>, key_fn: impl Fn(&T) -> K + 'static, render: impl Fn(&T) -> El + 'static) -> Self {
+
+        let anchor = self.anchor();
+        let (scope, mut items) = (self.scope, Vec::<(K, Scope, ObjectRef)>::new());
+        self.bind(move || {
+
+            // match keys
+            let keys = list.with(|l| l.iter().map(&key_fn).collect::<Vec<_>>());
+            let old_keys = items.iter().map(|i| i.0.clone()).collect::<Vec<_>>();
+            let plan = reconcile(&old_keys, &keys);
+
+            // reuse or render items
+            let mut old_items = items.drain(..).map(Some).collect::<Vec<_>>();
+            let new_items = list.with_untracked(|l| l.iter().zip(keys).zip(&plan).map(|((item, key), p)| match p {
+                Some((i, _)) => old_items[*i].take().unwrap(),
+                None => {
+                    let item_scope = scope.run(Scope::new);
+                    let el = item_scope.run(|| untrack(|| render(item)));
+                    (key, item_scope, el.element)
+                },
+            }).collect::<Vec<_>>());
+
+            // remove items
+            for (_, item_scope, element) in old_items.into_iter().flatten() {
+                item_scope.dispose();
+                commands::push(Command::Remove { node: element });
+            }
+
+            // insert new and moved items before the next item, starting from the end
+            let mut next = anchor;
+            for ((_, _, element), p) in new_items.iter().zip(&plan).rev() {
+                if !matches!(p, Some((_, true))) { commands::push(Command::InsertBefore { anchor: next, node: *element }); }
+                next = *element;
+            }
+            items = new_items;
+        });
+        self
+    }
+    // keeps `subscription` until the element is unmounted
+    pub fn subscription(self, subscription: Subscription) -> Self {
+        self.scope.own(subscription);
+        self
+    }
     pub fn once(self, cb: impl FnMut(ObjectRef) + 'static) -> Self {
 
         let cb = Rc::new(RefCell::new(cb));
-        cb.borrow_mut()(self.element);
+        self.scope.run(|| cb.borrow_mut()(self.element));
 
         self
     }
-    pub fn once_async<Fut: Future<Output = ()>>(self, cb: impl FnMut(ObjectRef) -> Fut + 'static) -> Self {
+    pub fn once_async<Fut: Future<Output = ()>>(self, mut cb: impl FnMut(ObjectRef) -> Fut + 'static) -> Self {
 
-        let cb = Rc::new(RefCell::new(cb));
+        let (element, token) = (self.element, self.token.clone());
+        Runtime::block_on(async move {
+            let _ = token.run(cb(element)).await;
+        });
 
+        self
+    }
+    // the task gets a token that is cancelled when the element unmounts
+    pub fn spawn<Fut: Future<Output = ()> + 'static>(self, cb: impl FnOnce(CancellationToken) -> Fut) -> Self {
+
+        let token = self.token.clone();
+        let future = cb(token.clone());
         Runtime::block_on(async move {
-            cb.borrow_mut()(self.element).await;
+            let _ = token.run(future).await;
         });
 
         self
     }
-    pub fn on(self, event: &str, cb: impl FnMut(ObjectRef) + 'static) -> Self {
+    fn listen(&self, event: &str, cb: impl FnMut(ObjectRef) + 'static) {
 
         let function_ref = crate::callbacks::create_callback(cb);
-        let code = &format!("{{}}.addEventListener('{}',{{}})", event);
-        Js::invoke(code, &[self.element.into(), function_ref.into()]);
+        commands::push(Command::Listen { node: self.element, event: event.to_owned(), callback: function_ref });
 
         self.callbacks.borrow_mut().push(function_ref);
 
+        // remove listener
+        let (element, event) = (self.element, event.to_owned());
+        self.scope.on_cleanup(move || {
+            commands::push(Command::Unlisten { node: element, event, callback: function_ref });
+            crate::callbacks::CALLBACK_HANDLERS.with(|s| { s.borrow_mut().remove(&function_ref); });
+        });
+    }
+    pub fn on(self, event: &str, mut cb: impl FnMut(ObjectRef) + 'static) -> Self {
+
+        let scope = self.scope;
+        self.listen(event, move |e| scope.run(|| cb(e)));
+
         self
     }
     pub fn on_async<Fut: Future<Output = ()>>(self, event: &str, cb: impl FnMut(ObjectRef) -> Fut + 'static) -> Self {
 
         let cb = Rc::new(RefCell::new(cb));
+        let (scope, token) = (self.scope, self.token.clone());
         let cb_async = move |e| {
-            let cb = cb.clone();
+            let (cb, token) = (cb.clone(), token.clone());
 
             Runtime::block_on(async move {
-                cb.borrow_mut()(e).await;
+                let future = scope.run(|| cb.borrow_mut()(e));
+                let _ = token.run(InScope { future: Box::pin(future), scope }).await;
             });
         };
+        self.listen(event, cb_async);
 
-        let function_ref = crate::callbacks::create_callback(cb_async);
-        let code = &format!("{{}}.addEventListener('{}',{{}})", event);
-        Js::invoke(code, &[self.element.into(), function_ref.into()]);
+        self
+    }
+    // the listener is removed when the element unmounts
+    pub fn events(&self, event: &str) -> EventStream {
+        EventStream::with_token(&self.element, event, &self.token)
+    }
+    pub fn text(self, text: &str) -> Self {
 
-        self.callbacks.borrow_mut().push(function_ref);
+        commands::push(Command::AppendText { parent: self.element, text: text.to_owned() });
 
         self
     }
-    pub fn text(self, text: &str) -> Self {
+    // appends a text node that is updated when the signal changes
+    pub fn text_signal<T: ToString + 'static>(self, signal: Signal<T>) -> Self {
 
-        let el = Js::invoke("return document.createTextNode({})", &[text.into()]).to_ref().unwrap();
-        Js::invoke("{}.appendChild({})", &[self.element.into(), el.into()]);
+        let el = commands::create_node();
+        commands::push(Command::CreateText { node: el, text: String::new() });
+        commands::push(Command::Append { parent: self.element, child: el });
+        self.release_on_cleanup(el);
+        self.bind(move || frame::update(Command::SetText { node: el, text: signal.with(|v| v.to_string()) }));
 
         self
     }
 }
 
+// polls `future` in `scope` so handlers can use the context of their element after awaiting
+struct InScope<F> { future: Pin<Box<F>>, scope: Scope }
+
+impl<F: Future> Future for InScope<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let scope = self.scope;
+        scope.run(|| self.future.as_mut().poll(cx))
+    }
+}
+
+// indices of the longest strictly increasing subsequence of `values`
+fn longest_increasing_subsequence(values: &[usize]) -> Vec<usize> {
+
+    // smallest tail of increasing subsequences by length and the previous index of each value
+    let (mut tails, mut previous) = (Vec::<usize>::new(), vec![None; values.len()]);
+    for (i, value) in values.iter().enumerate() {
+        let length = tails.partition_point(|t| values[*t] < *value);
+        if length > 0 { previous[i] = Some(tails[length - 1]); }
+        if length == tails.len() { tails.push(i); } else { tails[length] = i; }
+    }
+
+    let mut result = vec![];
+    let mut current = tails.last().copied();
+    while let Some(i) = current {
+        result.push(i);
+        current = previous[i];
+    }
+    result.reverse();
+    result
+}
+
+// for each new key, the index of the old item with the same key and whether it stays in place
+// NOTE: items in the longest increasing subsequence of old indices keep their order so only the rest are moved
+fn reconcile<K: Eq + Hash>(old: &[K], new: &[K]) -> Vec<Option<(usize, bool)>> {
+    let mut indices = old.iter().enumerate().map(|(i, k)| (k, i)).collect::<HashMap<_, _>>();
+    let sources = new.iter().map(|k| indices.remove(k)).collect::<Vec<_>>();
+    let reused = sources.iter().enumerate().filter_map(|(i, s)| Some((i, (*s)?))).collect::<Vec<_>>();
+    let mut plan = sources.iter().map(|s| s.map(|i| (i, false))).collect::<Vec<_>>();
+    let values = reused.iter().map(|(_, s)| *s).collect::<Vec<_>>();
+    for i in longest_increasing_subsequence(&values) {
+        plan[reused[i].0] = Some((reused[i].1, true));
+    }
+    plan
+}
+
+// case of a `Switch` that is rendered when `when` returns true for the signal value
+pub struct Match<T> { when: Box<dyn Fn(&T) -> bool>, render: Box<dyn Fn() -> El> }
+
+impl<T> Match<T> {
+    pub fn new(when: impl Fn(&T) -> bool + 'static, render: impl Fn() -> El + 'static) -> Self {
+        Self { when: Box::new(when), render: Box::new(render) }
+    }
+}
+
+// eg. `Switch::new(tab, vec![Match::new(|t| *t == Tab::Home, home)]).fallback(not_found)`
+pub struct Switch<T: 'static> { signal: Signal<T>, cases: Vec<Match<T>>, fallback: Option<Box<dyn Fn() -> El>> }
+
+impl<T: 'static> Switch<T> {
+    pub fn new(signal: Signal<T>, cases: Vec<Match<T>>) -> Self {
+        Self { signal, cases, fallback: None }
+    }
+    pub fn fallback(mut self, render: impl Fn() -> El + 'static) -> Self {
+        self.fallback = Some(Box::new(render));
+        self
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
+    use crate::signals::Signal;
+
     use super::*;
 
+    // rendered items and a signal created in the scope of each
+    type Renders = Rc<RefCell<Vec<(u32, Signal<u32>)>>>;
+
     #[test]
     fn test_element() {
 
-        let el = El::new("div").classes(&[])
-            .child(El::new("button").text("button 1"))
-            .child(El::new("button").text("button 2"));
-        assert_eq!(el, El::from(&ObjectRef::new(0)));
+        // record construction
+        let el = El::new("div").classes(&["list"])
+            .child(El::new("button").text("button 1"));
+        let button = ObjectRef::new(*el.element + 1);
+        assert_eq!(commands::is_node(el.element), true);
+        assert_eq!(Command::decode(&commands::take()), Ok(vec![
+            Command::Create { node: el.element, tag: "div".to_owned() },
+            Command::AddClass { node: el.element, name: "list".to_owned() },
+            Command::Create { node: button, tag: "button".to_owned() },
+            Command::AppendText { parent: button, text: "button 1".to_owned() },
+            Command::Append { parent: el.element, child: button },
+        ]));
+
+        // flush before invoking
+        el.mount(&ObjectRef::new(0));
+        assert_eq!(commands::take(), vec![]);
+
+    }
+
+    #[test]
+    fn test_unmount() {
+
+        // cancel pending task on unmount
+        let has_run = Rc::new(RefCell::new(false));
+        let has_run_clone = has_run.clone();
+        let child = El::new("button").spawn(move |token| async move {
+            token.cancelled().await.unwrap_err();
+            *has_run_clone.borrow_mut() = true;
+        });
+        let (child_token, button) = (child.token.clone(), child.element);
+        let el = El::new("div").child(child.on("click", |_| {}));
+        commands::take();
+        el.unmount();
+        assert_eq!(child_token.is_cancelled(), true);
+        assert_eq!(*has_run.borrow(), false);
+
+        // remove listeners and release node ids
+        let released = Command::decode(&commands::take()).unwrap().into_iter()
+            .filter(|c| matches!(c, Command::Release { .. } | Command::Unlisten { .. })).collect::<Vec<_>>();
+        // NOTE: node ids are released after the batch of commands is applied
+        assert_eq!(released, vec![
+            Command::Release { node: button },
+            Command::Unlisten { node: button, event: "click".to_owned(), callback: ObjectRef::new(0) },
+            Command::Release { node: el.element },
+        ]);
+    }
+
+    #[test]
+    fn test_on_async() {
+
+        // read context before and after awaiting
+        let values: Rc<RefCell<Vec<Option<u32>>>> = Default::default();
+        let values_clone = values.clone();
+        let (tx, rx) = crate::sync::oneshot::channel::<()>();
+        let rx = Rc::new(RefCell::new(Some(rx)));
+        let el = Scope::new().run(|| {
+            crate::signals::provide_context(1u32);
+            El::new("button").on_async("click", move |_| {
+                let (values, rx) = (values_clone.clone(), rx.borrow_mut().take().unwrap());
+                async move {
+                    values.borrow_mut().push(crate::signals::use_context::<u32>());
+                    rx.await.unwrap();
+                    values.borrow_mut().push(crate::signals::use_context::<u32>());
+                }
+            })
+        });
+        crate::callbacks::handle_callback(*el.callbacks.borrow()[0], 0);
+        tx.send(()).unwrap();
+        let last_callback = crate::callbacks::CALLBACK_HANDLERS.with(|s| s.borrow().keys().map(|k| **k).max().unwrap());
+        crate::callbacks::handle_callback(last_callback, 0);
+        assert_eq!(*values.borrow(), vec![Some(1), Some(1)]);
+    }
+
+    #[test]
+    fn test_bindings() {
+
+        // update while mounted
+        let runs = Rc::new(RefCell::new(0));
+        let runs_clone = runs.clone();
+        let signal = Signal::new(false);
+        let el = El::new("button")
+            .text_signal(signal)
+            .class_when("active", signal)
+            .attr_fn("disabled", "", move || { *runs_clone.borrow_mut() += 1; signal.get() });
+        signal.set(true);
+        assert_eq!(*runs.borrow(), 2);
+
+        // unsubscribe on unmount
+        el.unmount();
+        signal.set(false);
+        assert_eq!(*runs.borrow(), 2);
+
+        // defer updates to the next frame
+        frame::defer_writes(true);
+        commands::take();
+        let el = El::new("p").text_signal(signal);
+        assert_eq!(Command::decode(&commands::take()).unwrap().len(), 4);
+        signal.set(true);
+        assert_eq!(commands::is_empty(), true);
+        el.unmount();
+        frame::flush();
+        assert_eq!(Command::decode(&commands::take()).unwrap().iter().any(|c| matches!(c, Command::SetText { .. })), false);
+        frame::defer_writes(false);
+    }
+
+    #[test]
+    fn test_show() {
+
+        // render on change
+        let renders: Rc<RefCell<Vec<Signal<u32>>>> = Default::default();
+        let renders_clone = renders.clone();
+        let when = Signal::new(true);
+        let el = El::new("div").show(when, move || {
+            renders_clone.borrow_mut().push(Signal::new(0));
+            El::new("span")
+        }, || El::new("p"));
+        when.set(true);
+        assert_eq!(renders.borrow().len(), 1);
+
+        // dispose hidden branch
+        when.set(false);
+        assert_eq!(renders.borrow()[0].is_disposed(), true);
+        el.unmount();
+    }
+
+    #[test]
+    fn test_to_html() {
+
+        // render bindings, branches and lists
+        let (name, when, items) = (Signal::new("a & b".to_owned()), Signal::new(true), Signal::new(vec![1, 2]));
+        let el = El::new("div").attr_signal("title", name).class_when("open", when)
+            .child(El::new("input").attr("type", "checkbox").attr_fn("checked", "", move || when.get()))
+            .text("name: ").text_signal(name)
+            .show(when, || El::new("span").text("on"), || El::new("span").text("off"))
+            .child(El::new("ul").each(items, |i| *i, |i| El::new("li").text(&i.to_string())));
+        let expected = r#"<div title="a &amp; b" class="open"><input type="checkbox" checked>name: <!--,-->a &amp; b<span>on</span><!----><ul><li>1</li><li>2</li><!----></ul></div>"#;
+        assert_eq!(el.to_html(), expected);
+
+        // render updates
+        when.set(false);
+        items.set(vec![2, 3]);
+        let expected = r#"<div title="a &amp; b" class=""><input type="checkbox">name: <!--,-->a &amp; b<span>off</span><!----><ul><li>2</li><li>3</li><!----></ul></div>"#;
+        assert_eq!(el.to_html(), expected);
+    }
+
+    #[test]
+    fn test_hydrate() {
+
+        // NOTE: creating the listener doesn't apply the pending commands
+        let clicks = Signal::new(0);
+        let el = El::new("div").child(El::new("button").text("+").on("click", move |_| clicks.update(|c| *c += 1)));
+        let count = crate::callbacks::CALLBACK_HANDLERS.with(|s| s.borrow().len());
+        assert_eq!(count, 1);
+
+        // skip creating nodes
+        assert_eq!(el.hydrate(&ObjectRef::new(0)), true);
+        assert_eq!(commands::take(), vec![]);
+        assert_eq!(el.to_html(), "");
+
+        // mount when already created
+        let el = El::new("div").once(|el| { Js::invoke("{}.focus()", &[el.into()]); });
+        assert_eq!(el.hydrate(&ObjectRef::new(0)), false);
+        assert_eq!(el.to_html(), "<div></div>");
+
+        // set properties after hydrating
+        let value = Signal::new("a".to_owned());
+        let el = El::new("input").prop_signal("value", value);
+        assert_eq!(commands::is_empty(), false);
+        assert_eq!(el.hydrate(&ObjectRef::new(0)), true);
+        assert_eq!(el.to_html(), "");
+    }
+
+    #[test]
+    fn test_switch() {
+
+        #[derive(Debug, Clone, Copy, PartialEq)]
+        enum Tab { Home, Settings, Other }
+
+        let renders: Rc<RefCell<Vec<&str>>> = Default::default();
+        let (home_clone, settings_clone, fallback_clone) = (renders.clone(), renders.clone(), renders.clone());
+        let tab = Signal::new(Tab::Home);
+        let cases = vec![
+            Match::new(|t| *t == Tab::Home, move || { home_clone.borrow_mut().push("home"); El::new("div") }),
+            Match::new(|t| *t == Tab::Settings, move || { settings_clone.borrow_mut().push("settings"); El::new("div") }),
+        ];
+        let switch = Switch::new(tab, cases).fallback(move || { fallback_clone.borrow_mut().push("fallback"); El::new("div") });
+        let _el = El::new("div").switch(switch);
+        tab.set(Tab::Settings);
+        tab.set(Tab::Settings);
+        tab.set(Tab::Other);
+        assert_eq!(*renders.borrow(), vec!["home", "settings", "fallback"]);
+    }
+
+    #[test]
+    fn test_reconcile() {
+
+        assert_eq!(longest_increasing_subsequence(&[3, 0, 1, 2]), vec![1, 2, 3]);
+        assert_eq!(longest_increasing_subsequence(&[2, 1, 0]).len(), 1);
+
+        // move only the last item
+        let plan = reconcile(&["a", "b", "c", "d"], &["d", "a", "b", "c"]);
+        assert_eq!(plan, vec![Some((3, false)), Some((0, true)), Some((1, true)), Some((2, true))]);
+
+        // create new and duplicate keys
+        let plan = reconcile(&["a", "b"], &["c", "b", "b"]);
+        assert_eq!(plan, vec![None, Some((1, true)), None]);
+    }
+
+    #[test]
+    fn test_each() {
+
+        // render new items only
+        let renders: Renders = Default::default();
+        let renders_clone = renders.clone();
+        let list = Signal::new(vec![1, 2, 3]);
+        let el = El::new("ul").each(list, |v| *v, move |v| {
+            renders_clone.borrow_mut().push((*v, Signal::new(*v)));
+            El::new("li")
+        });
+        list.set(vec![3, 1, 4]);
+        assert_eq!(renders.borrow().iter().map(|r| r.0).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+
+        // dispose removed items
+        let is_disposed = |v: u32| renders.borrow().iter().find(|r| r.0 == v).unwrap().1.is_disposed();
+        assert_eq!((is_disposed(1), is_disposed(2)), (false, true));
+        el.unmount();
+        assert_eq!(is_disposed(1), true);
+    }
+
+    #[test]
+    fn test_scope() {
+
+        // subscribe in element scope
+        let logs: Rc<RefCell<Vec<u32>>> = Default::default();
+        let signal = Signal::new(0);
+        let logs_clone = logs.clone();
+        let subscription = signal.on(move |v| { logs_clone.borrow_mut().push(v); });
+        let el = El::new("div").child(El::new("span").subscription(subscription).on("click", |_| {}));
+        signal.set(1);
 
+        // dispose on unmount
+        el.unmount();
+        signal.set(2);
+        assert_eq!(*logs.borrow(), vec![0, 1]);
+        let count = crate::callbacks::CALLBACK_HANDLERS.with(|s| s.borrow().len());
+        assert_eq!(count, 0);
     }
 
 }
@@ -0,0 +1,161 @@
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    future::{Future, Pending},
+    ops::Deref,
+    pin::Pin,
+    rc::Rc,
+    task::{Context, Poll, Waker}
+};
+
+use crate::callbacks::{create_callback, CALLBACK_HANDLERS};
+use crate::invoke::{Js, JsValue, ObjectRef};
+use crate::runtime::{Cancellable, CancellationToken};
+use crate::sync::Stream;
+
+// retained event object that is deallocated on drop
+#[derive(Debug, PartialEq, Eq)]
+pub struct Event(ObjectRef);
+
+impl Deref for Event {
+    type Target = ObjectRef;
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Drop for Event {
+    fn drop(&mut self) {
+        Js::deallocate(self.0);
+    }
+}
+
+impl From<&Event> for JsValue { fn from(e: &Event) -> Self { Self::Ref(e.0) } }
+
+struct Queue { events: VecDeque<Event>, capacity: Option<usize>, waker: Option<Waker> }
+
+pub struct EventStream { target: ObjectRef, event: String, function_ref: ObjectRef, queue: Rc<RefCell<Queue>>, cancelled: Option<Cancellable<Pending<()>>> }
+
+impl EventStream {
+    pub fn new(target: &ObjectRef, event: &str) -> Self {
+        Self::listen(target, event, None)
+    }
+    // the listener is removed with the `signal` of the token when it's cancelled and the stream ends after the buffered events
+    pub fn with_token(target: &ObjectRef, event: &str, token: &CancellationToken) -> Self {
+        Self::listen(target, event, Some(token))
+    }
+    fn listen(target: &ObjectRef, event: &str, token: Option<&CancellationToken>) -> Self {
+
+        // buffer events until polled
+        let queue = Rc::new(RefCell::new(Queue { events: VecDeque::new(), capacity: None, waker: None }));
+        let queue_clone = queue.clone();
+        let function_ref = create_callback(move |e| {
+            let mut queue = queue_clone.borrow_mut();
+            if queue.capacity.is_some_and(|c| queue.events.len() >= c) { queue.events.pop_front(); }
+            queue.events.push_back(Event(Js::retain(e)));
+            if let Some(waker) = queue.waker.take() { waker.wake(); }
+        });
+
+        // add listener
+        let signal = token.map_or(JsValue::Undefined, |t| t.signal().into());
+        let code = &format!("{{}}.addEventListener('{}',{{}},{{ signal: {{}} }})", event);
+        Js::invoke(code, &[target.into(), function_ref.into(), signal]);
+
+        let cancelled = token.map(|t| t.cancelled());
+        Self { target: target.to_owned(), event: event.to_owned(), function_ref, queue, cancelled }
+    }
+    // keeps only the most recent events, eg. `capacity(1)` coalesces pointer moves between polls
+    pub fn capacity(self, capacity: usize) -> Self {
+        assert!(capacity > 0, "Capacity should be greater than zero");
+        let mut queue = self.queue.borrow_mut();
+        let excess = queue.events.len().saturating_sub(capacity);
+        queue.events.drain(..excess);
+        queue.capacity = Some(capacity);
+        drop(queue);
+        self
+    }
+}
+
+impl Stream for EventStream {
+    type Item = Event;
+
+    // NOTE: never ends without a token, the listener is active until the stream is dropped
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        let mut queue = this.queue.borrow_mut();
+        match queue.events.pop_front() {
+            Some(event) => Poll::Ready(Some(event)),
+            None if this.cancelled.as_mut().is_some_and(|c| Pin::new(c).poll(cx).is_ready()) => Poll::Ready(None),
+            None => {
+                queue.waker = Some(cx.waker().to_owned());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+impl Drop for EventStream {
+    fn drop(&mut self) {
+
+        // remove listener
+        let code = &format!("{{}}.removeEventListener('{}',{{}})", self.event);
+        Js::invoke(code, &[self.target.into(), self.function_ref.into()]);
+
+        // remove callback
+        CALLBACK_HANDLERS.with(|s| { s.borrow_mut().remove(&self.function_ref); });
+        Js::deallocate(self.function_ref);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::callbacks::handle_callback;
+
+    use super::*;
+
+    fn poll_next(stream: &mut EventStream) -> Poll<Option<Event>> {
+        let mut context = Context::from_waker(Waker::noop());
+        Pin::new(&mut stream.next()).poll(&mut context)
+    }
+
+    #[test]
+    fn test_event_stream() {
+
+        // buffer events
+        let mut stream = EventStream::new(&ObjectRef::new(0), "pointermove");
+        assert!(poll_next(&mut stream).is_pending());
+        handle_callback(*stream.function_ref, 0);
+        handle_callback(*stream.function_ref, 0);
+        assert!(matches!(poll_next(&mut stream), Poll::Ready(Some(_))));
+        assert!(matches!(poll_next(&mut stream), Poll::Ready(Some(_))));
+        assert!(poll_next(&mut stream).is_pending());
+
+        // keep latest
+        let mut stream = stream.capacity(1);
+        (0..3).for_each(|_| handle_callback(*stream.function_ref, 0));
+        assert_eq!(stream.queue.borrow().events.len(), 1);
+        assert!(matches!(poll_next(&mut stream), Poll::Ready(Some(_))));
+
+        // remove listener
+        drop(stream);
+        let count = CALLBACK_HANDLERS.with(|s| s.borrow().len());
+        assert_eq!(count, 0);
+    }
+
+    #[test]
+    fn test_event_stream_token() {
+
+        // end after buffered events when cancelled
+        let token = CancellationToken::new();
+        let mut stream = EventStream::with_token(&ObjectRef::new(0), "click", &token);
+        assert!(poll_next(&mut stream).is_pending());
+        handle_callback(*stream.function_ref, 0);
+        token.cancel();
+        assert!(matches!(poll_next(&mut stream), Poll::Ready(Some(_))));
+        assert!(matches!(poll_next(&mut stream), Poll::Ready(None)));
+    }
+
+}
@@ -0,0 +1,273 @@
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::callbacks::{create_callback, CALLBACK_HANDLERS};
+use crate::commands::Command;
+use crate::invoke::{Js, JsValue, ObjectRef};
+use crate::runtime::Runtime;
+use crate::signals::OnDrop;
+
+thread_local! {
+    static DEFER_WRITES: Cell<bool> = const { Cell::new(false) };
+    static IS_SCHEDULED: Cell<bool> = const { Cell::new(false) };
+    static FLUSH_CALLBACK: Cell<Option<ObjectRef>> = const { Cell::new(None) };
+    static PENDING_WRITES: RefCell<Vec<(ObjectRef, String, JsValue)>> = const { RefCell::new(vec![]) };
+    static PENDING_UPDATES: RefCell<Vec<Command>> = const { RefCell::new(vec![]) };
+}
+
+fn frame_timestamp(object_ref: ObjectRef) -> f64 {
+    Js::invoke("return {}", &[object_ref.into()]).to_num().unwrap()
+}
+
+// resolves with the frame timestamp
+pub async fn request_animation_frame() -> f64 {
+    let object_ref = Runtime::promise("window.requestAnimationFrame({})", move |c| vec![c.into()]).await;
+    let timestamp = frame_timestamp(object_ref);
+    Js::deallocate(object_ref);
+    timestamp
+}
+
+struct LoopState { function_ref: Option<ObjectRef>, request_id: f64, active: bool, running: bool }
+
+// runs every frame until cancelled or dropped
+#[must_use]
+pub struct FrameLoop { state: Rc<RefCell<LoopState>> }
+
+pub fn on_frame(mut cb: impl FnMut(f64) + 'static) -> FrameLoop {
+
+    let state = Rc::new(RefCell::new(LoopState { function_ref: None, request_id: 0.0, active: true, running: false }));
+    let state_clone = state.clone();
+    let function_ref = create_callback(move |e| {
+
+        // run callback
+        state_clone.borrow_mut().running = true;
+        cb(frame_timestamp(e));
+        let mut state = state_clone.borrow_mut();
+        state.running = false;
+
+        // request next frame or remove the callback when cancelled by `cb`
+        match (state.active, state.function_ref) {
+            (true, Some(function_ref)) => {
+                state.request_id = Js::invoke("return window.requestAnimationFrame({})", &[function_ref.into()]).to_num().unwrap();
+            },
+            (false, Some(function_ref)) => {
+                state.function_ref = None;
+                CALLBACK_HANDLERS.with(|s| { s.borrow_mut().remove(&function_ref); });
+                Js::deallocate(function_ref);
+            },
+            (_, None) => {},
+        }
+    });
+
+    let request_id = Js::invoke("return window.requestAnimationFrame({})", &[function_ref.into()]).to_num().unwrap();
+    *state.borrow_mut() = LoopState { function_ref: Some(function_ref), request_id, active: true, running: false };
+    FrameLoop { state }
+}
+
+impl FrameLoop {
+    pub fn is_active(&self) -> bool {
+        self.state.borrow().active
+    }
+    pub fn cancel(&self) {
+        let mut state = self.state.borrow_mut();
+        if !state.active { return; }
+        state.active = false;
+        Js::invoke("window.cancelAnimationFrame({})", &[state.request_id.into()]);
+
+        // NOTE: a callback can't be removed while it runs so it's removed after it returns when cancelled from the loop itself
+        if state.running { return; }
+        let function_ref = state.function_ref.take().unwrap();
+        CALLBACK_HANDLERS.with(|s| { s.borrow_mut().remove(&function_ref); });
+        Js::deallocate(function_ref);
+    }
+}
+
+impl Drop for FrameLoop {
+    fn drop(&mut self) {
+        self.cancel();
+    }
+}
+
+// when enabled, `write` and the updates of element bindings are deferred to the next frame and only
+// the last value of each node property is applied
+pub fn defer_writes(enabled: bool) {
+    DEFER_WRITES.set(enabled);
+    if !enabled { flush(); }
+}
+
+// runs `f` with writes applied immediately, eg. the first run of bindings so elements are complete when mounted
+pub fn immediate<R>(f: impl FnOnce() -> R) -> R {
+    let enabled = DEFER_WRITES.replace(false);
+    let _reset = OnDrop(|| DEFER_WRITES.set(enabled));
+    f()
+}
+
+// sets a node property, eg. `write(el, "innerHTML", "hello".into())`
+pub fn write(node: ObjectRef, property: &str, value: JsValue) {
+
+    if !DEFER_WRITES.get() {
+        Js::invoke("{}[{}] = {}", &[node.into(), property.into(), value]);
+        return;
+    }
+
+    // coalesce writes
+    PENDING_WRITES.with_borrow_mut(|s| {
+        match s.iter_mut().find(|(n, p, _)| *n == node && p == property) {
+            Some(write) => write.2 = value,
+            None => s.push((node, property.to_owned(), value)),
+        }
+    });
+    schedule();
+}
+
+// node property that is changed by an update command
+fn update_key(command: &Command) -> Option<(ObjectRef, &'static str, String)> {
+    match command {
+        Command::SetAttribute { node, name, .. } | Command::RemoveAttribute { node, name } => Some((*node, "attribute", name.to_owned())),
+        Command::ToggleClass { node, name, .. } => Some((*node, "class", name.to_owned())),
+        Command::SetStyle { node, property, .. } => Some((*node, "style", property.to_owned())),
+        Command::SetProperty { node, name, .. } => Some((*node, "property", name.to_owned())),
+        Command::SetText { node, .. } => Some((*node, "text", String::new())),
+        _ => None,
+    }
+}
+
+// pushes a dom command that updates a node property, eg. by bindings
+// NOTE: other commands are applied immediately so nodes are created, moved and released in order
+pub fn update(command: Command) {
+
+    let Some(key) = update_key(&command).filter(|_| DEFER_WRITES.get()) else {
+        crate::commands::push(command);
+        return;
+    };
+
+    // coalesce updates
+    PENDING_UPDATES.with_borrow_mut(|s| {
+        match s.iter_mut().find(|c| update_key(c).as_ref() == Some(&key)) {
+            Some(update) => *update = command,
+            None => s.push(command),
+        }
+    });
+    schedule();
+}
+
+// drops the pending updates and writes of a node, eg. when it's released
+pub fn discard(node: ObjectRef) {
+    PENDING_UPDATES.with_borrow_mut(|s| s.retain(|c| c.target() != node));
+    PENDING_WRITES.with_borrow_mut(|s| s.retain(|(n, _, _)| *n != node));
+}
+
+// requests a flush on the next frame unless one is pending
+fn schedule() {
+    if IS_SCHEDULED.replace(true) { return; }
+    let function_ref = FLUSH_CALLBACK.get().unwrap_or_else(|| {
+        let function_ref = create_callback(|_| flush());
+        FLUSH_CALLBACK.set(Some(function_ref));
+        function_ref
+    });
+    Js::invoke("window.requestAnimationFrame({})", &[function_ref.into()]);
+}
+
+// applies pending commands, updates and writes in a single call each
+pub fn flush() {
+    IS_SCHEDULED.set(false);
+    PENDING_UPDATES.take().into_iter().for_each(crate::commands::push);
+    crate::commands::flush();
+    let writes = PENDING_WRITES.take();
+    if writes.is_empty() { return; }
+
+    let code = "{}[{}] = {};".repeat(writes.len());
+    let params = writes.into_iter().flat_map(|(n, p, v)| [n.into(), p.into(), v]).collect::<Vec<_>>();
+    Js::invoke(&code, &params);
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::callbacks::handle_callback;
+
+    use super::*;
+
+    #[test]
+    fn test_frame_loop() {
+
+        // run every frame
+        let frames = Rc::new(RefCell::new(0));
+        let frames_clone = frames.clone();
+        let frame_loop = on_frame(move |_| { *frames_clone.borrow_mut() += 1; });
+        let function_ref = frame_loop.state.borrow().function_ref.unwrap();
+        handle_callback(*function_ref, 0);
+        handle_callback(*function_ref, 0);
+        assert_eq!(*frames.borrow(), 2);
+
+        // cancel loop
+        drop(frame_loop);
+        let count = CALLBACK_HANDLERS.with(|s| s.borrow().len());
+        assert_eq!(count, 0);
+
+        // cancel from the loop itself
+        let handle: Rc<RefCell<Option<FrameLoop>>> = Default::default();
+        let handle_clone = handle.clone();
+        *handle.borrow_mut() = Some(on_frame(move |_| handle_clone.borrow().as_ref().unwrap().cancel()));
+        let function_ref = handle.borrow().as_ref().unwrap().state.borrow().function_ref.unwrap();
+        handle_callback(*function_ref, 0);
+        assert_eq!(handle.borrow().as_ref().unwrap().is_active(), false);
+        let count = CALLBACK_HANDLERS.with(|s| s.borrow().len());
+        assert_eq!(count, 0);
+        handle.take();
+    }
+
+    #[test]
+    fn test_deferred_writes() {
+
+        // coalesce writes
+        defer_writes(true);
+        write(ObjectRef::new(1), "innerHTML", "a".into());
+        write(ObjectRef::new(2), "innerHTML", "b".into());
+        write(ObjectRef::new(1), "innerHTML", "c".into());
+        PENDING_WRITES.with_borrow(|s| {
+            assert_eq!(s.len(), 2);
+            assert!(matches!(&s[0], (n, _, JsValue::Str(v)) if *n == ObjectRef::new(1) && v == "c"));
+        });
+
+        // flush on frame
+        let function_ref = FLUSH_CALLBACK.get().unwrap();
+        handle_callback(*function_ref, 0);
+        assert_eq!(PENDING_WRITES.with_borrow(|s| s.len()), 0);
+
+        // coalesce updates and apply them on frame
+        let (node, other) = (crate::commands::create_node(), crate::commands::create_node());
+        update(Command::SetText { node, text: "a".to_owned() });
+        update(Command::SetAttribute { node, name: "title".to_owned(), value: "a".to_owned() });
+        update(Command::RemoveAttribute { node, name: "title".to_owned() });
+        update(Command::SetText { node, text: "b".to_owned() });
+        update(Command::Create { node: other, tag: "div".to_owned() });
+        assert_eq!(PENDING_UPDATES.take(), vec![
+            Command::SetText { node, text: "b".to_owned() },
+            Command::RemoveAttribute { node, name: "title".to_owned() },
+        ]);
+        assert_eq!(Command::decode(&crate::commands::take()).unwrap(), vec![Command::Create { node: other, tag: "div".to_owned() }]);
+        update(Command::SetText { node, text: "c".to_owned() });
+        handle_callback(*function_ref, 0);
+        assert_eq!(PENDING_UPDATES.with_borrow(|s| s.len()), 0);
+
+        // drop updates of released nodes
+        update(Command::SetText { node, text: "d".to_owned() });
+        update(Command::SetText { node: other, text: "d".to_owned() });
+        discard(node);
+        assert_eq!(PENDING_UPDATES.take(), vec![Command::SetText { node: other, text: "d".to_owned() }]);
+
+        // update immediately
+        immediate(|| update(Command::SetText { node, text: "e".to_owned() }));
+        assert_eq!(PENDING_UPDATES.with_borrow(|s| s.len()), 0);
+        assert_eq!(DEFER_WRITES.get(), true);
+        crate::commands::take();
+
+        // write immediately
+        defer_writes(false);
+        write(ObjectRef::new(1), "innerHTML", "d".into());
+        assert_eq!(PENDING_WRITES.with_borrow(|s| s.len()), 0);
+    }
+
+}
@@ -0,0 +1,230 @@
+
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::rc::Rc;
+
+use crate::invoke::Js;
+use crate::signals::{batch, Signal};
+use crate::storage::Codec;
+
+// encoded value of each tracked signal
+type Revision = BTreeMap<String, String>;
+
+type Setter = Rc<dyn Fn(&str)>;
+
+struct Inner { revisions: VecDeque<Revision>, cursor: usize, capacity: usize, setters: HashMap<String, Setter> }
+
+impl Inner {
+    fn record(&mut self, key: &str, value: String) {
+
+        // NOTE: skip values set by restoring a revision
+        let mut revision = self.revisions[self.cursor].clone();
+        if revision.get(key) == Some(&value) { return; }
+        revision.insert(key.to_owned(), value);
+
+        // drop revisions after the current one and the oldest when full
+        self.revisions.truncate(self.cursor + 1);
+        self.revisions.push_back(revision);
+        if self.revisions.len() > self.capacity { self.revisions.pop_front(); }
+        self.cursor = self.revisions.len() - 1;
+    }
+}
+
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('\n', "\\n").replace('\t', "\\t")
+}
+
+fn unescape(value: &str) -> String {
+    let (mut result, mut chars) = (String::new(), value.chars());
+    while let Some(c) = chars.next() {
+        match (c, c == '\\') {
+            (_, true) => match chars.next() { Some('n') => result.push('\n'), Some('t') => result.push('\t'), Some(c) => result.push(c), None => {} },
+            (c, false) => result.push(c),
+        }
+    }
+    result
+}
+
+// bounded record of the values of opted-in signals that can be stepped through
+#[derive(Clone)]
+pub struct History { inner: Rc<RefCell<Inner>> }
+
+impl std::fmt::Debug for History {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let inner = self.inner.borrow();
+        f.debug_struct("History").field("revision", &inner.cursor).field("len", &inner.revisions.len()).finish()
+    }
+}
+
+impl History {
+    pub fn new(capacity: usize) -> Self {
+        assert!(capacity > 0, "Capacity should be greater than zero");
+        let inner = Inner { revisions: VecDeque::from([Revision::new()]), cursor: 0, capacity, setters: HashMap::new() };
+        Self { inner: Rc::new(RefCell::new(inner)) }
+    }
+    // records every update of `signal` under `key`
+    pub fn track<T: Codec + 'static>(&self, key: &str, signal: Signal<T>) {
+
+        // add to current revision
+        let mut inner = self.inner.borrow_mut();
+        let cursor = inner.cursor;
+        inner.revisions[cursor].insert(key.to_owned(), signal.with_untracked(|v| v.encode()));
+        let setter = move |v: &str| { if let Ok(v) = T::decode(v) { let _ = signal.try_set(v); } };
+        inner.setters.insert(key.to_owned(), Rc::new(setter));
+        drop(inner);
+
+        // record updates
+        let (inner, key) = (self.inner.clone(), key.to_owned());
+        signal.subscribe(move || {
+            let Ok(value) = signal.try_with(|v| v.encode()) else { return; };
+            inner.borrow_mut().record(&key, value);
+        }).detach();
+    }
+    pub fn revision(&self) -> usize {
+        self.inner.borrow().cursor
+    }
+    pub fn len(&self) -> usize {
+        self.inner.borrow().revisions.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+    // sets all tracked signals to their values at `revision` and runs their subscribers once
+    pub fn jump(&self, revision: usize) -> bool {
+        let mut inner = self.inner.borrow_mut();
+        if revision >= inner.revisions.len() { return false; }
+        inner.cursor = revision;
+        let setters = inner.revisions[revision].iter()
+            .filter_map(|(k, v)| Some((inner.setters.get(k)?.clone(), v.to_owned())))
+            .collect::<Vec<_>>();
+        drop(inner);
+
+        batch(|| setters.into_iter().for_each(|(setter, value)| setter(&value)));
+        true
+    }
+    pub fn back(&self) -> bool {
+        let revision = self.revision();
+        revision > 0 && self.jump(revision - 1)
+    }
+    pub fn forward(&self) -> bool {
+        self.jump(self.revision() + 1)
+    }
+    // one line per revision with tab separated keys and values, the first line is the current revision
+    pub fn export(&self) -> String {
+        let inner = self.inner.borrow();
+        let lines = inner.revisions.iter().map(|r| r.iter().map(|(k, v)| format!("{}\t{}", escape(k), escape(v))).collect::<Vec<_>>().join("\t"));
+        std::iter::once(inner.cursor.to_string()).chain(lines).collect::<Vec<_>>().join("\n")
+    }
+    pub fn import(&self, data: &str) -> Result<(), String> {
+
+        // parse revisions
+        // NOTE: `lines` would drop the line of a trailing empty revision
+        let mut lines = data.split('\n');
+        let mut cursor = lines.next().and_then(|l| l.parse::<usize>().ok()).ok_or("Invalid revision".to_string())?;
+        let mut revisions = lines.map(|line| {
+            let parts = line.split('\t').map(unescape).collect::<Vec<_>>();
+            if line.is_empty() { return Ok(Revision::new()); }
+            if parts.len() % 2 != 0 { return Err("Invalid entry".to_string()); }
+            Ok(parts.chunks(2).map(|c| (c[0].to_owned(), c[1].to_owned())).collect::<Revision>())
+        }).collect::<Result<VecDeque<_>, _>>()?;
+        if cursor >= revisions.len() { return Err("Invalid revision".to_string()); }
+
+        // keep at most `capacity` revisions around the current one
+        let mut inner = self.inner.borrow_mut();
+        let dropped = revisions.len().saturating_sub(inner.capacity).min(cursor);
+        revisions.drain(..dropped);
+        revisions.truncate(inner.capacity);
+        cursor -= dropped;
+
+        // restore
+        inner.revisions = revisions;
+        drop(inner);
+        self.jump(cursor);
+        Ok(())
+    }
+    // saves the exported history as a file
+    pub fn download(&self, filename: &str) {
+        let code = r#"
+            const url = URL.createObjectURL(new Blob([{}], { type: 'text/plain' }));
+            const a = document.createElement('a');
+            a.href = url; a.download = {}; a.click();
+            URL.revokeObjectURL(url);
+        "#;
+        Js::invoke(code, &[self.export().into(), filename.into()]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::signals::create_effect;
+
+    use super::*;
+
+    #[test]
+    fn test_history() {
+
+        // record updates
+        let history = History::new(10);
+        let name = Signal::new("a".to_owned());
+        let count = Signal::new(0);
+        history.track("name", name);
+        history.track("count", count);
+        name.set("b\tc".to_owned());
+        count.set(1);
+        assert_eq!((history.revision(), history.len()), (2, 3));
+
+        // restore atomically
+        let logs: Rc<RefCell<Vec<(String, u32)>>> = Default::default();
+        let logs_clone = logs.clone();
+        create_effect(move || logs_clone.borrow_mut().push((name.get(), count.get())));
+        assert!(history.jump(0));
+        assert!(!history.back());
+        assert!(history.forward());
+        assert_eq!(*logs.borrow(), vec![("b\tc".to_owned(), 1), ("a".to_owned(), 0), ("b\tc".to_owned(), 0)]);
+        assert_eq!(history.len(), 3);
+
+        // export and import
+        let data = history.export();
+        count.set(5);
+        assert_eq!(history.len(), 3);
+        history.import(&data).unwrap();
+        assert_eq!((name.get(), count.get(), history.revision()), ("b\tc".to_owned(), 0, 1));
+        assert!(history.import("invalid").is_err());
+    }
+
+    #[test]
+    fn test_capacity() {
+        let history = History::new(2);
+        let count = Signal::new(0);
+        history.track("count", count);
+        (1..5).for_each(|v| count.set(v));
+        assert_eq!(history.len(), 2);
+        assert!(history.jump(0));
+        assert_eq!(count.get(), 3);
+    }
+
+    #[test]
+    fn test_import() {
+
+        // round-trip empty revisions
+        let history = History::new(10);
+        assert_eq!(history.export(), "0\n");
+        history.import(&history.export()).unwrap();
+        assert_eq!((history.revision(), history.len()), (0, 1));
+        history.import("1\n\n").unwrap();
+        assert_eq!((history.revision(), history.len()), (1, 2));
+
+        // trim to capacity
+        let history = History::new(2);
+        let count = Signal::new(0);
+        history.track("count", count);
+        history.import("3\ncount\t0\ncount\t1\ncount\t2\ncount\t3").unwrap();
+        assert_eq!((history.revision(), history.len(), count.get()), (1, 2, 3));
+        history.import("0\ncount\t0\ncount\t1\ncount\t2").unwrap();
+        assert_eq!((history.revision(), history.len(), count.get()), (0, 2, 0));
+        assert!(history.forward());
+        assert_eq!(count.get(), 1);
+    }
+
+}
@@ -0,0 +1,330 @@
+
+use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
+
+use crate::commands::{write_ref, write_str, Command};
+use crate::invoke::ObjectRef;
+
+// elements without children that are rendered without a closing tag
+const VOID_ELEMENTS: [&str; 13] = ["area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track", "wbr"];
+
+// attributes that are rendered without a value when set
+const BOOLEAN_ATTRIBUTES: [&str; 24] = [
+    "allowfullscreen", "async", "autofocus", "autoplay", "checked", "controls", "default", "defer",
+    "disabled", "formnovalidate", "hidden", "inert", "ismap", "loop", "multiple", "muted",
+    "nomodule", "novalidate", "open", "playsinline", "readonly", "required", "reversed", "selected",
+];
+
+// elements whose text is rendered as is
+const RAW_TEXT_ELEMENTS: [&str; 2] = ["script", "style"];
+
+// NOTE: adjacent text nodes are merged when html is parsed so they're separated with a comment
+// that hydration skips, other comments are the anchors of conditional and list rendering
+pub const TEXT_SEPARATOR: &str = ",";
+
+thread_local! {
+    static DOCUMENT: RefCell<Document> = RefCell::new(Document::default());
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Kind { Element { tag: String, attributes: Vec<(String, String)> }, Text(String), Comment }
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Node { pub kind: Kind, pub parent: Option<ObjectRef>, pub children: Vec<ObjectRef> }
+
+// in-memory node tree built from dom commands
+#[derive(Debug, Default)]
+pub struct Document { nodes: HashMap<ObjectRef, Node>, texts: u32 }
+
+pub fn escape_text(value: &str) -> String {
+    value.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+pub fn escape_attribute(value: &str) -> String {
+    escape_text(value).replace('"', "&quot;")
+}
+
+// NOTE: raw text ends at the first closing tag of its element, which scripts and styles read the same with `<\/`
+fn escape_raw_text(tag: &str, value: &str) -> String {
+    let (closing, lowercase) = (format!("</{}", tag), value.to_ascii_lowercase());
+    let (mut result, mut start) = (String::new(), 0);
+    for (index, _) in lowercase.match_indices(&closing) {
+        result.push_str(&value[start..index]);
+        result.push_str("<\\/");
+        start = index + 2;
+    }
+    result.push_str(&value[start..]);
+    result
+}
+
+impl Document {
+    fn create(&mut self, node: ObjectRef, kind: Kind) {
+        self.nodes.insert(node, Node { kind, parent: None, children: vec![] });
+    }
+    fn detach(&mut self, node: ObjectRef) {
+        let Some(parent) = self.nodes.get_mut(&node).and_then(|n| n.parent.take()) else { return; };
+        if let Some(parent) = self.nodes.get_mut(&parent) { parent.children.retain(|c| *c != node); }
+    }
+    fn insert(&mut self, parent: ObjectRef, index: Option<usize>, child: ObjectRef) {
+        if !self.nodes.contains_key(&parent) || !self.nodes.contains_key(&child) { return; }
+        self.detach(child);
+        let children = &mut self.nodes.get_mut(&parent).unwrap().children;
+        children.insert(index.unwrap_or(children.len()).min(children.len()), child);
+        self.nodes.get_mut(&child).unwrap().parent = Some(parent);
+    }
+    fn attributes(&mut self, node: ObjectRef) -> Option<&mut Vec<(String, String)>> {
+        match &mut self.nodes.get_mut(&node)?.kind { Kind::Element { attributes, .. } => Some(attributes), _ => None }
+    }
+    fn set_attribute(&mut self, node: ObjectRef, name: &str, value: String) {
+        let Some(attributes) = self.attributes(node) else { return; };
+        match attributes.iter_mut().find(|(k, _)| k == name) {
+            Some(attribute) => attribute.1 = value,
+            None => attributes.push((name.to_owned(), value)),
+        }
+    }
+    fn get_attribute(&mut self, node: ObjectRef, name: &str) -> String {
+        let attributes = self.attributes(node);
+        attributes.and_then(|a| a.iter().find(|(k, _)| k == name)).map(|(_, v)| v.to_owned()).unwrap_or_default()
+    }
+    fn toggle_class(&mut self, node: ObjectRef, name: &str, enabled: bool) {
+        let value = self.get_attribute(node, "class");
+        let mut classes = value.split_whitespace().filter(|c| *c != name).collect::<Vec<_>>();
+        if enabled { classes.push(name); }
+        self.set_attribute(node, "class", classes.join(" "));
+    }
+    fn set_style(&mut self, node: ObjectRef, property: &str, value: &str) {
+        let style = self.get_attribute(node, "style");
+        let mut properties = style.split(';').map(str::trim).filter(|p| !p.is_empty() && p.split(':').next().map(str::trim) != Some(property)).collect::<Vec<_>>();
+        let declaration = format!("{}: {}", property, value);
+        if !value.is_empty() { properties.push(&declaration); }
+        self.set_attribute(node, "style", properties.join("; "));
+    }
+    // NOTE: released nodes are detached with their children, eg. text nodes that have no id in the browser
+    fn release(&mut self, node: ObjectRef) {
+        self.detach(node);
+        let Some(node) = self.nodes.remove(&node) else { return; };
+        node.children.into_iter().for_each(|c| self.release(c));
+    }
+    pub fn apply(&mut self, commands: &[Command]) {
+        for command in commands {
+            match command {
+                Command::Create { node, tag } => self.create(*node, Kind::Element { tag: tag.to_owned(), attributes: vec![] }),
+                Command::CreateText { node, text } => self.create(*node, Kind::Text(text.to_owned())),
+                Command::CreateComment { node } => self.create(*node, Kind::Comment),
+                Command::SetAttribute { node, name, value } => self.set_attribute(*node, name, value.to_owned()),
+                Command::AddClass { node, name } => self.toggle_class(*node, name, true),
+                Command::Append { parent, child } => self.insert(*parent, None, *child),
+                // NOTE: appended texts have no id in the browser so their ids are outside the range of node ids
+                Command::AppendText { parent, text } => {
+                    self.texts += 1;
+                    let node = ObjectRef::new(self.texts);
+                    self.create(node, Kind::Text(text.to_owned()));
+                    self.insert(*parent, None, node);
+                },
+                Command::Clear { node } => {
+                    let children = self.nodes.get(node).map(|n| n.children.clone()).unwrap_or_default();
+                    children.into_iter().for_each(|c| self.detach(c));
+                },
+                // NOTE: properties aren't rendered, eg. the `value` of an input is set when it's hydrated
+                Command::Listen { .. } | Command::Unlisten { .. } | Command::SetProperty { .. } => {},
+                Command::RemoveAttribute { node, name } => { if let Some(a) = self.attributes(*node) { a.retain(|(k, _)| k != name); } },
+                Command::ToggleClass { node, name, enabled } => self.toggle_class(*node, name, *enabled),
+                Command::SetStyle { node, property, value } => self.set_style(*node, property, value),
+                Command::SetText { node, text } => { if let Some(Node { kind: Kind::Text(t), .. }) = self.nodes.get_mut(node) { *t = text.to_owned(); } },
+                Command::InsertBefore { anchor, node } => {
+                    let Some(parent) = self.nodes.get(anchor).and_then(|n| n.parent) else { continue; };
+                    self.detach(*node);
+                    let index = self.nodes[&parent].children.iter().position(|c| c == anchor);
+                    self.insert(parent, index, *node);
+                },
+                Command::Remove { node } => self.detach(*node),
+                Command::Release { node } => self.release(*node),
+            }
+        }
+    }
+    pub fn get(&self, node: ObjectRef) -> Option<&Node> {
+        self.nodes.get(&node)
+    }
+    fn render(&self, node: ObjectRef, html: &mut String) {
+        let Some(Node { kind, children, .. }) = self.nodes.get(&node) else { return; };
+        match kind {
+            Kind::Element { tag, attributes } => {
+
+                // open tag
+                html.push_str(&format!("<{}", tag));
+                for (name, value) in attributes {
+                    match BOOLEAN_ATTRIBUTES.contains(&name.as_str()) {
+                        true => html.push_str(&format!(" {}", name)),
+                        false => html.push_str(&format!(" {}=\"{}\"", name, escape_attribute(value))),
+                    }
+                }
+                html.push('>');
+                if VOID_ELEMENTS.contains(&tag.as_str()) { return; }
+
+                // children
+                let is_raw = RAW_TEXT_ELEMENTS.contains(&tag.as_str());
+                let mut is_text = false;
+                for child in children {
+                    match &self.nodes[child].kind {
+                        Kind::Text(text) if is_raw => html.push_str(&escape_raw_text(tag, text)),
+                        Kind::Text(text) => {
+                            if is_text { html.push_str(&format!("<!--{}-->", TEXT_SEPARATOR)); }
+                            html.push_str(&escape_text(text));
+                        },
+                        _ => self.render(*child, html),
+                    }
+                    is_text = matches!(self.nodes[child].kind, Kind::Text(_));
+                }
+                html.push_str(&format!("</{}>", tag));
+            },
+            Kind::Text(text) => html.push_str(&escape_text(text)),
+            Kind::Comment => html.push_str("<!---->"),
+        }
+    }
+    // node and its children in document order
+    fn encode_tree(&self, node: ObjectRef, buffer: &mut Vec<u8>, tree: &mut HashSet<ObjectRef>) {
+        let Some(Node { kind, children, .. }) = self.nodes.get(&node) else { return; };
+        tree.insert(node);
+        match kind {
+            Kind::Element { tag, attributes } => {
+                buffer.push(0);
+                write_ref(buffer, node);
+                write_str(buffer, tag);
+                buffer.extend_from_slice(&(attributes.len() as u32).to_le_bytes());
+                attributes.iter().for_each(|(k, v)| { write_str(buffer, k); write_str(buffer, v); });
+                buffer.extend_from_slice(&(children.len() as u32).to_le_bytes());
+                children.iter().for_each(|c| self.encode_tree(*c, buffer, tree));
+            },
+            Kind::Text(text) => { buffer.push(1); write_ref(buffer, node); write_str(buffer, text); },
+            Kind::Comment => { buffer.push(2); write_ref(buffer, node); },
+        }
+    }
+    // html of the node and its children
+    pub fn to_html(&self, node: ObjectRef) -> String {
+        let mut html = String::new();
+        self.render(node, &mut html);
+        html
+    }
+}
+
+// splits pending commands into the tree of `root` that is matched with the server rendered dom, the
+// commands that don't use its nodes and the commands of its nodes that still have to be applied, ie.
+// listeners, properties and nodes outside the tree
+// NOTE: the tree is encoded in document order as kind, id, tag, attributes and children count for
+// elements, kind, id and text for text nodes and kind and id for comments
+pub fn hydration(commands: Vec<Command>, root: ObjectRef) -> (Vec<u8>, Vec<Command>, Vec<Command>) {
+    let mut document = Document::default();
+    document.apply(&commands);
+    let (mut buffer, mut tree) = (vec![], HashSet::new());
+    document.encode_tree(root, &mut buffer, &mut tree);
+    let is_kept = |c: &Command| matches!(c, Command::Listen { .. } | Command::Unlisten { .. } | Command::Release { .. } | Command::SetProperty { .. }) || !tree.contains(&c.target());
+
+    // NOTE: later commands of nodes used with the tree are kept after it too
+    let (mut before, mut after, mut used) = (vec![], vec![], tree.clone());
+    for command in commands.into_iter().filter(is_kept) {
+        let nodes = command.nodes();
+        if !nodes.iter().any(|n| used.contains(n)) { before.push(command); continue; }
+        used.extend(nodes);
+        after.push(command);
+    }
+    (buffer, before, after)
+}
+
+pub fn apply(commands: &[Command]) {
+    DOCUMENT.with_borrow_mut(|s| s.apply(commands));
+}
+
+pub fn to_html(node: ObjectRef) -> String {
+    DOCUMENT.with_borrow(|s| s.to_html(node))
+}
+
+// removes all nodes, eg. after a page is rendered
+pub fn clear() {
+    DOCUMENT.take();
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::commands::create_node;
+
+    use super::*;
+
+    #[test]
+    fn test_html() {
+
+        // escape, boolean attributes and void elements
+        let (div, input, script) = (create_node(), create_node(), create_node());
+        let mut document = Document::default();
+        document.apply(&[
+            Command::Create { node: div, tag: "div".to_owned() },
+            Command::SetAttribute { node: div, name: "title".to_owned(), value: "a \"b\" & <c>".to_owned() },
+            Command::AddClass { node: div, name: "card".to_owned() },
+            Command::ToggleClass { node: div, name: "active".to_owned(), enabled: true },
+            Command::SetStyle { node: div, property: "color".to_owned(), value: "red".to_owned() },
+            Command::AppendText { parent: div, text: "1 < 2".to_owned() },
+            Command::AppendText { parent: div, text: "!".to_owned() },
+            Command::Create { node: input, tag: "input".to_owned() },
+            Command::SetAttribute { node: input, name: "disabled".to_owned(), value: "".to_owned() },
+            Command::Append { parent: div, child: input },
+            Command::Create { node: script, tag: "script".to_owned() },
+            Command::AppendText { parent: script, text: "a && b </SCRIPT><b> </p>".to_owned() },
+            Command::Append { parent: div, child: script },
+        ]);
+        let expected = r#"<div title="a &quot;b&quot; &amp; &lt;c&gt;" class="card active" style="color: red">1 &lt; 2<!--,-->!<input disabled><script>a && b <\/SCRIPT><b> </p></script></div>"#;
+        assert_eq!(document.to_html(div), expected);
+        let texts = document.get(div).unwrap().children.iter().filter(|c| !crate::commands::is_node(**c)).count();
+        assert_eq!(texts, 2);
+
+        // update
+        let anchor = create_node();
+        document.apply(&[
+            Command::ToggleClass { node: div, name: "card".to_owned(), enabled: false },
+            Command::RemoveAttribute { node: div, name: "title".to_owned() },
+            Command::SetStyle { node: div, property: "color".to_owned(), value: "".to_owned() },
+            Command::Clear { node: div },
+            Command::CreateComment { node: anchor },
+            Command::Append { parent: div, child: anchor },
+            Command::InsertBefore { anchor, node: input },
+            Command::Remove { node: script },
+            Command::Release { node: script },
+        ]);
+        assert_eq!(document.to_html(div), r#"<div class="active" style=""><input disabled><!----></div>"#);
+        assert_eq!(document.get(script), None);
+    }
+
+    #[test]
+    fn test_hydration() {
+
+        // keep listeners and nodes outside the tree, after the tree when they use its nodes
+        let (div, span, other) = (create_node(), create_node(), create_node());
+        let commands = vec![
+            Command::Create { node: div, tag: "div".to_owned() },
+            Command::Create { node: span, tag: "span".to_owned() },
+            Command::AppendText { parent: span, text: "a".to_owned() },
+            Command::Append { parent: div, child: span },
+            Command::Listen { node: span, event: "click".to_owned(), callback: ObjectRef::new(1) },
+            Command::Create { node: other, tag: "p".to_owned() },
+            Command::AppendText { parent: other, text: "b".to_owned() },
+            Command::Append { parent: span, child: other },
+            Command::Remove { node: other },
+        ];
+        let (tree, before, after) = hydration(commands, div);
+        assert_eq!(before, vec![
+            Command::Create { node: other, tag: "p".to_owned() },
+            Command::AppendText { parent: other, text: "b".to_owned() },
+        ]);
+        assert_eq!(after, vec![
+            Command::Listen { node: span, event: "click".to_owned(), callback: ObjectRef::new(1) },
+            Command::Append { parent: span, child: other },
+            Command::Remove { node: other },
+        ]);
+
+        // div with no attributes and 1 child
+        assert_eq!(tree[0], 0);
+        assert_eq!(tree[1..5], div.to_le_bytes());
+        assert_eq!(tree[5..12], [3, 0, 0, 0, b'd', b'i', b'v']);
+        assert_eq!(tree[12..20], [0, 0, 0, 0, 1, 0, 0, 0]);
+    }
+
+}
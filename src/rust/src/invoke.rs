@@ -1,15 +1,16 @@
 
 use std::ops::Deref;
 
-#[cfg(not(test))]
+#[cfg(target_arch = "wasm32")]
 extern "C" {
     fn __invoke(c_ptr: *const u8, c_len: u32, p_ptr: *const u8, p_len: u32) -> u64;
     fn __deallocate(object_id: *const u8);
 }
 
-#[cfg(test)]
+// NOTE: outside the browser, eg. in tests or when rendering html, invoking does nothing
+#[cfg(not(target_arch = "wasm32"))]
 unsafe fn __invoke(_c_ptr: *const u8, _c_len: u32, _p_ptr: *const u8, _p_len: u32) -> u64 { 0 }
-#[cfg(test)]
+#[cfg(not(target_arch = "wasm32"))]
 unsafe fn __deallocate(_object_id: *const u8) {}
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
@@ -31,7 +32,7 @@ impl Deref for ObjectRef {
 
 // NOTE: Numbers in Javascript are represented by 64-bits floats
 // https://tc39.es/ecma262/multipage/ecmascript-data-types-and-values.html#sec-ecmascript-language-types-number-type
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum JsValue {
     Undefined,
     Null,
@@ -70,7 +71,7 @@ impl JsValue {
             JsValue::Str(s) => [vec![4], (s.as_ptr() as u32).to_le_bytes().to_vec(), s.len().to_le_bytes().to_vec()].concat(),
             JsValue::Bool(b) => vec![if *b { 5 } else { 6 }],
             JsValue::Ref(i) => [vec![7], i.0.to_le_bytes().to_vec()].concat(),
-            JsValue::Buffer(b) => [vec![8], b.to_owned()].concat(),
+            JsValue::Buffer(b) => [vec![8], (b.as_ptr() as u32).to_le_bytes().to_vec(), b.len().to_le_bytes().to_vec()].concat(),
         }
     }
 
@@ -100,46 +101,46 @@ impl JsValue {
     pub fn to_bool(&self) -> Result<bool, String> {
         match &self {
             JsValue::Bool(b) => Ok(b.to_owned()),
-            #[cfg(not(test))] _ => Err("Invalid type".to_string()),
-            #[cfg(test)] _ => Ok(true),
+            #[cfg(target_arch = "wasm32")] _ => Err("Invalid type".to_string()),
+            #[cfg(not(target_arch = "wasm32"))] _ => Ok(true),
         }
     }
 
     pub fn to_str(&self) -> Result<String, String> {
         match &self {
             JsValue::Str(s) => Ok(s.to_string()),
-            #[cfg(not(test))] _ => Err("Invalid type".to_string()),
-            #[cfg(test)] _ => Ok("".to_string()),
+            #[cfg(target_arch = "wasm32")] _ => Err("Invalid type".to_string()),
+            #[cfg(not(target_arch = "wasm32"))] _ => Ok("".to_string()),
         }
     }
 
     pub fn to_num(&self) -> Result<f64, String> {
         match &self {
             JsValue::Number(s) => Ok(s.to_owned()),
-            #[cfg(not(test))] _ => Err("Invalid type".to_string()),
-            #[cfg(test)] _ => Ok(0.into()),
+            #[cfg(target_arch = "wasm32")] _ => Err("Invalid type".to_string()),
+            #[cfg(not(target_arch = "wasm32"))] _ => Ok(0.into()),
         }
     }
     pub fn to_ref(&self) -> Result<ObjectRef, String> {
         match &self {
             JsValue::Ref(s) => Ok(s.to_owned()),
-            #[cfg(not(test))] _ => Err("Invalid type".to_string()),
-            #[cfg(test)] _ => Ok(ObjectRef(0)),
+            #[cfg(target_arch = "wasm32")] _ => Err("Invalid type".to_string()),
+            #[cfg(not(target_arch = "wasm32"))] _ => Ok(ObjectRef(0)),
         }
     }
     pub fn to_buffer(&self) -> Result<Vec<u8>, String> {
         match &self {
             JsValue::Buffer(s) => Ok(s.to_owned()),
-            #[cfg(not(test))] _ => Err("Invalid type".to_string()),
-            #[cfg(test)] _ => Ok(vec![]),
+            #[cfg(target_arch = "wasm32")] _ => Err("Invalid type".to_string()),
+            #[cfg(not(target_arch = "wasm32"))] _ => Ok(vec![]),
         }
     }
 
     pub fn to_bigint(&self) -> Result<i64, String> {
         match &self {
             JsValue::BigInt(s) => Ok(s.to_owned()),
-            #[cfg(not(test))] _ => Err("Invalid type".to_string()),
-            #[cfg(test)] _ => Ok(0.into()),
+            #[cfg(target_arch = "wasm32")] _ => Err("Invalid type".to_string()),
+            #[cfg(not(target_arch = "wasm32"))] _ => Ok(0.into()),
         }
     }
 }
@@ -161,6 +162,8 @@ impl Js {
         format!("function({}) {{ {} }}", params_names.join(","), code_params)
     }
     pub fn invoke<'a>(code: &'a str, params: &[JsValue]) -> JsValue {
+        // NOTE: pending dom commands are applied when a node is used so building elements doesn't touch the dom
+        if params.iter().any(|p| matches!(p, JsValue::Ref(r) if crate::commands::is_node(*r))) { crate::commands::flush(); }
         let code = Self::__code(code, params);
         let params = params.iter().flat_map(JsValue::serialize).collect::<Vec<_>>();
         let r_packed = unsafe { __invoke(code.as_ptr(), code.len() as u32, params.as_ptr(), params.len() as u32) };
@@ -171,6 +174,11 @@ impl Js {
     pub fn deallocate(object_id: ObjectRef) {
         unsafe { __deallocate(*object_id as *const u8) };
     }
+    // NOTE: callback params are deallocated when the callback returns, retain them to use them later
+    pub fn retain(object_id: ObjectRef) -> ObjectRef {
+        let object_id = Js::invoke("objects.push({}); return objects.length - 1", &[object_id.into()]).to_num().unwrap();
+        ObjectRef::new(object_id as u32)
+    }
 }
 
 #[cfg(test)]
@@ -212,7 +220,11 @@ mod tests {
         assert_eq!(JsValue::Ref(ObjectRef(42)).serialize(), [vec![7], 42u32.to_le_bytes().to_vec()].concat());
 
         // buffer
-        assert_eq!(JsValue::Buffer(vec![1, 2, 3]).serialize(), [vec![8], vec![1, 2, 3]].concat());
+        let buffer = vec![1, 2, 3];
+        let buffer_ptr = buffer.as_ptr() as u32;
+        let buffer_len = buffer.len() as u64;
+        let expected = [vec![8], buffer_ptr.to_le_bytes().to_vec(), buffer_len.to_le_bytes().to_vec()].concat();
+        assert_eq!(JsValue::Buffer(buffer).serialize(), expected);
 
     }
 
@@ -2,10 +2,21 @@
 pub mod callbacks;
 pub mod allocations;
 pub mod runtime;
+pub mod sync;
+pub mod worker;
 pub mod invoke;
+pub mod commands;
+pub mod html;
 
 pub mod signals;
+pub mod collections;
+pub mod resource;
+pub mod storage;
+pub mod tabs;
+pub mod history;
 pub mod element;
+pub mod events;
+pub mod frame;
 pub mod router;
 
 // Use: crate::println!("{}", 42);
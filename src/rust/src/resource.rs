@@ -0,0 +1,148 @@
+
+use std::future::Future;
+
+use crate::runtime::{CancellationToken, Runtime};
+use crate::signals::{create_effect, on_cleanup, untrack, Scope, Signal};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResourceState<T, E> { Loading, Ready(T), Failed(E) }
+
+// value loaded by an async fetcher that refetches when its source changes
+// NOTE: a request is cancelled when a newer one starts so stale responses are discarded
+pub struct Resource<T: 'static, E: 'static> { state: Signal<ResourceState<T, E>>, trigger: Signal<u32>, scope: Scope }
+
+impl<T, E> Clone for Resource<T, E> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<T, E> Copy for Resource<T, E> {}
+
+impl<T, E> std::fmt::Debug for Resource<T, E> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Resource").field("state", &self.state).finish()
+    }
+}
+
+impl<T: Clone + 'static, E: Clone + 'static> Resource<T, E> {
+    pub fn new<S: Clone + 'static, Fut: Future<Output = Result<T, E>> + 'static>(source: Signal<S>, fetcher: impl Fn(S) -> Fut + 'static) -> Self {
+
+        // NOTE: the effect is owned by the resource so it stops refetching when disposed and keeps the last state
+        let (state, trigger) = (Signal::new(ResourceState::Loading), Signal::new(0));
+        let scope = Scope::new();
+        scope.run(|| create_effect(move || {
+
+            // track source and refetches
+            let value = source.get();
+            trigger.get();
+
+            // cancel request when superseded or disposed
+            let token = CancellationToken::new();
+            let token_clone = token.clone();
+            on_cleanup(move || token_clone.cancel());
+
+            // fetch
+            untrack(|| {
+                state.set(ResourceState::Loading);
+                let future = fetcher(value);
+                Runtime::block_on(async move {
+                    let Ok(result) = token.run(future).await else { return; };
+                    let _ = state.try_set(match result { Ok(v) => ResourceState::Ready(v), Err(e) => ResourceState::Failed(e) });
+                });
+            });
+        }));
+        Self { state, trigger, scope }
+    }
+    pub fn state(&self) -> ResourceState<T, E> {
+        self.state.get()
+    }
+    pub fn get(&self) -> Option<T> {
+        self.state.with(|s| match s { ResourceState::Ready(v) => Some(v.clone()), _ => None })
+    }
+    pub fn is_loading(&self) -> bool {
+        self.state.with(|s| matches!(s, ResourceState::Loading))
+    }
+    pub fn refetch(&self) {
+        self.trigger.update(|v| *v += 1);
+    }
+    // sets the value before the request that changes it completes
+    pub fn mutate(&self, value: T) {
+        self.state.set(ResourceState::Ready(value));
+    }
+    pub fn dispose(&self) {
+        self.scope.dispose();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+    use std::rc::Rc;
+
+    use crate::callbacks::{handle_callback, CALLBACK_HANDLERS};
+    use crate::sync::oneshot;
+
+    use super::*;
+
+    // the wake callback of the task that was woken last
+    fn last_callback() -> u32 {
+        CALLBACK_HANDLERS.with(|s| s.borrow().keys().map(|k| **k).max().unwrap())
+    }
+
+    #[test]
+    fn test_resource() {
+
+        // load on create
+        let fetches = Rc::new(RefCell::new(0));
+        let fetches_clone = fetches.clone();
+        let source = Signal::new(1);
+        let resource = Resource::new(source, move |id: u32| {
+            *fetches_clone.borrow_mut() += 1;
+            async move { if id == 0 { Err("not found") } else { Ok(id * 10) } }
+        });
+        assert_eq!(resource.state(), ResourceState::Ready(10));
+
+        // refetch on change
+        source.set(0);
+        assert_eq!(resource.state(), ResourceState::Failed("not found"));
+        resource.mutate(5);
+        assert_eq!(resource.get(), Some(5));
+        resource.refetch();
+        assert_eq!(resource.state(), ResourceState::Failed("not found"));
+
+        // stop on dispose
+        resource.dispose();
+        source.set(2);
+        resource.refetch();
+        assert_eq!(*fetches.borrow(), 3);
+        assert_eq!(resource.state(), ResourceState::Failed("not found"));
+    }
+
+    #[test]
+    fn test_stale_response() {
+
+        let senders: Rc<RefCell<Vec<oneshot::Sender<u32>>>> = Default::default();
+        let senders_clone = senders.clone();
+        let source = Signal::new(1);
+        let resource = Resource::new(source, move |_: u32| {
+            let (tx, rx) = oneshot::channel();
+            senders_clone.borrow_mut().push(tx);
+            async move { rx.await.map_err(|_| ()) }
+        });
+        assert_eq!(resource.is_loading(), true);
+
+        // NOTE: woken tasks are polled again when their wake callback runs
+        source.set(2);
+        handle_callback(last_callback(), 0);
+        let first = senders.borrow_mut().remove(0);
+        assert!(first.send(10).is_err());
+        assert_eq!(resource.is_loading(), true);
+
+        // keep latest
+        let second = senders.borrow_mut().remove(0);
+        second.send(20).unwrap();
+        handle_callback(last_callback(), 0);
+        assert_eq!(resource.state(), ResourceState::Ready(20));
+    }
+
+}
@@ -1,16 +1,28 @@
 
 
+use std::cell::RefCell;
 use std::collections::HashMap;
+use std::rc::Rc;
 
-use crate::invoke::{Js, ObjectRef};
+use crate::invoke::{Js, JsValue, ObjectRef};
 use crate::element::El;
+use crate::signals::Scope;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::html::{escape_attribute, escape_text};
 
-#[derive(Debug, Clone)]
-pub struct Page { pub path: String, pub element: El, pub title: Option<String> }
+// NOTE: pages are rendered on navigation so their signals are freed when navigating away
+#[derive(Clone)]
+pub struct Page { pub path: String, pub render: Rc<dyn Fn() -> El>, pub title: Option<String> }
+
+impl std::fmt::Debug for Page {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Page").field("path", &self.path).field("title", &self.title).finish()
+    }
+}
 
 impl Page {
-    pub fn new(path: &str, element: El) -> Self {
-        Self { path: path.to_owned(), element, title: None }
+    pub fn new(path: &str, render: impl Fn() -> El + 'static) -> Self {
+        Self { path: path.to_owned(), render: Rc::new(render), title: None }
     }
     pub fn ttile(mut self, title: String) -> Self {
         self.title = Some(title);
@@ -18,29 +30,58 @@ impl Page {
     }
 }
 
+// marks the root of generated pages so the router hydrates them instead of rendering them again
+pub const HYDRATE_ATTRIBUTE: &str = "data-tinyweb-hydrate";
+
+// NOTE: pages are rendered in a scope owned by the scope the router is created in so they can use its context
+fn render(page: &Page, parent: Option<Scope>) -> (Scope, El) {
+    let scope = parent.map(|p| p.run(Scope::new)).unwrap_or_else(Scope::root);
+    let element = scope.run(|| (page.render)());
+    (scope, element)
+}
+
 #[derive(Debug, Default)]
-pub struct Router { pub root: Option<ObjectRef>, pub pages: HashMap::<String, Page> }
+pub struct Router { pub root: Option<ObjectRef>, pub pages: HashMap::<String, Page>, current: RefCell<Option<(Scope, El)>>, parent: Option<Scope> }
 
 impl Router {
     pub fn new(root: &str, pages: &[Page]) -> Self {
         let body = Js::invoke("return document.querySelector({})", &[root.into()]).to_ref().unwrap();
         let pathname = Js::invoke("return window.location.pathname", &[]).to_str().unwrap();
-        let page = pages.iter().find(|&s| *s.path == pathname).unwrap_or(&pages[0]);
-        page.element.mount(&body);
+
+        // NOTE: static hosts serve generated pages with a trailing slash, eg. `/page1/`
+        let pathname = pathname.trim_end_matches('/');
 
         let mut default_page = pages.first().cloned().unwrap();
         default_page.path = "/".to_owned();
 
+        let page = pages.iter().find(|&s| s.path == pathname).unwrap_or(&pages[0]).to_owned();
         let mut pages = pages.iter().map(|p| (p.path.clone(), p.to_owned())).collect::<Vec<_>>();
         pages.push((default_page.path.clone(), default_page.to_owned()));
-        Self { pages: HashMap::from_iter(pages), root: Some(body) }
+        let parent = Scope::current();
+        let router = Self { pages: HashMap::from_iter(pages), root: Some(body), current: Default::default(), parent };
+
+        // reuse the html of generated pages, they are marked by `render_document`
+        let code = format!("const root = {{}}; const has_html = root.hasAttribute('{0}'); root.removeAttribute('{0}'); return has_html", HYDRATE_ATTRIBUTE);
+        let has_html = matches!(Js::invoke(&code, &[body.into()]), JsValue::Bool(true));
+        router.mount(&page, has_html);
+        router
+    }
+    fn mount(&self, page: &Page, hydrate: bool) {
+        let (scope, element) = render(page, self.parent);
+        match hydrate {
+            true => { element.hydrate(self.root.as_ref().unwrap()); },
+            false => element.mount(self.root.as_ref().unwrap()),
+        }
+        *self.current.borrow_mut() = Some((scope, element));
     }
     pub fn navigate(&self, route: &str) {
 
         // unmount page
-        let pathname = Js::invoke("return window.location.pathname", &[]).to_str().unwrap();
-        let (_, current_page) = self.pages.iter().find(|&(s, _)| *s == pathname).unwrap();
-        current_page.element.unmount();
+        let current = self.current.borrow_mut().take();
+        if let Some((scope, element)) = current {
+            element.unmount();
+            scope.dispose();
+        }
 
         // set html
         let body = self.root.as_ref().unwrap();
@@ -48,7 +89,7 @@ impl Router {
 
         // mount new page
         let page = self.pages.get(route).unwrap();
-        page.element.mount(&body);
+        self.mount(page, false);
 
         // push state
         let page_str = page.title.to_owned().unwrap_or_default();
@@ -56,3 +97,104 @@ impl Router {
 
     }
 }
+
+// html document of the page that loads the client app from `/main.js` and `/{wasm}`, see `generate`
+#[cfg(not(target_arch = "wasm32"))]
+pub fn render_document(page: &Page, wasm: &str, head: &str) -> String {
+    let (scope, element) = render(page, Scope::current());
+    let body = element.to_html();
+    element.unmount();
+    scope.dispose();
+
+    // NOTE: nodes of a page aren't used after it's rendered, even if they weren't released
+    crate::commands::take();
+    crate::html::clear();
+
+    let title = page.title.as_deref().map(|t| format!("<title>{}</title>", escape_text(t))).unwrap_or_default();
+    let scripts = format!(r#"<script src="/main.js"></script><script type="application/wasm" src="/{}"></script>"#, escape_attribute(wasm));
+    format!(r#"<!DOCTYPE html><html><head><meta charset="utf-8">{}{}{}</head><body {}>{}</body></html>"#, title, head, scripts, HYDRATE_ATTRIBUTE, body)
+}
+
+// writes each page to `<dir>/<path>/index.html` and the first page to `<dir>/index.html` too, so they can be
+// served by any static host, `main.js` and the wasm file should be copied to `dir`
+// NOTE: the router should use `body` as its root, it hydrates the page when the client app loads
+#[cfg(not(target_arch = "wasm32"))]
+pub fn generate(dir: &str, pages: &[Page], wasm: &str, head: &str) -> Result<Vec<String>, String> {
+    let mut paths = pages.iter().map(|p| (p.path.trim_matches('/').to_owned(), p)).collect::<Vec<_>>();
+    if let Some(page) = pages.first() { paths.push((String::new(), page)); }
+
+    let mut files = vec![];
+    for (path, page) in paths {
+        let dir = std::path::Path::new(dir).join(&path);
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Can't create {}: {}", dir.display(), e))?;
+        let file = dir.join("index.html");
+        std::fs::write(&file, render_document(page, wasm, head)).map_err(|e| format!("Can't write {}: {}", file.display(), e))?;
+        files.push(file.display().to_string());
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::signals::{provide_context, use_context, Signal};
+
+    use super::*;
+
+    #[test]
+    fn test_navigate() {
+
+        // render page in scope
+        let signals: Rc<RefCell<Vec<Signal<u32>>>> = Default::default();
+        let signals_clone = signals.clone();
+        let page = Page::new("/page1", move || {
+            signals_clone.borrow_mut().push(Signal::new(0));
+            El::new("div")
+        });
+        let router = Router::new("body", &[page]);
+        assert_eq!(signals.borrow()[0].is_disposed(), false);
+
+        // dispose on navigate
+        router.navigate("/page1");
+        assert_eq!(signals.borrow()[0].is_disposed(), true);
+        assert_eq!(signals.borrow()[1].is_disposed(), false);
+    }
+
+    #[test]
+    fn test_context() {
+
+        // read context in pages
+        let themes: Rc<RefCell<Vec<Option<&str>>>> = Default::default();
+        let themes_clone = themes.clone();
+        let page = Page::new("/page1", move || {
+            themes_clone.borrow_mut().push(use_context::<&str>());
+            El::new("div")
+        });
+        let _router = Scope::root().run(|| {
+            provide_context("dark");
+            Router::new("body", &[page])
+        });
+        assert_eq!(*themes.borrow(), vec![Some("dark")]);
+    }
+
+    #[test]
+    fn test_generate() {
+
+        // render with title and scripts
+        let leaked = El::new("p");
+        let page = Page::new("/about", || El::new("h1").text("About")).ttile("About <us>".to_owned());
+        let document = render_document(&page, "app.wasm", "");
+        let expected = r#"<!DOCTYPE html><html><head><meta charset="utf-8"><title>About &lt;us&gt;</title><script src="/main.js"></script><script type="application/wasm" src="/app.wasm"></script></head><body data-tinyweb-hydrate><h1>About</h1></body></html>"#;
+        assert_eq!(document, expected);
+        assert_eq!(leaked.to_html(), "");
+
+        // write pages
+        let dir = std::env::temp_dir().join(format!("tinyweb-generate-{}", std::process::id()));
+        let files = generate(dir.to_str().unwrap(), &[page, Page::new("/blog/post", || El::new("p"))], "app.wasm", "").unwrap();
+        assert_eq!(files.len(), 3);
+        assert_eq!(std::fs::read_to_string(dir.join("blog/post/index.html")).unwrap().contains("<p></p></body>"), true);
+        assert_eq!(std::fs::read_to_string(dir.join("index.html")).unwrap(), expected);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+}
@@ -1,13 +1,14 @@
 use std::{
     cell::RefCell,
+    collections::HashMap,
     future::Future,
     mem::ManuallyDrop,
     pin::Pin,
-    rc::Rc,
+    rc::{Rc, Weak},
     task::{Context, Poll, RawWaker, RawWakerVTable, Waker}
 };
 
-use crate::callbacks::{create_async_callback, create_callback};
+use crate::callbacks::{create_async_callback, create_callback, CALLBACK_HANDLERS};
 use crate::invoke::{Js, JsValue, ObjectRef};
 
 pub enum FutureState<T> { Init, Pending(Waker), Ready(T) }
@@ -55,7 +56,8 @@ impl Runtime {
         fn wake_fn<T: 'static>(ptr: *const ()) {
             let future = unsafe { FutureRc::<T>::from_raw(ptr as *const _) };
             let function_ref = create_callback(move |_| { Runtime::poll(&future); });
-            Js::invoke("window.setTimeout({},0)", &[function_ref.into()]);
+            // NOTE: `window` is not available in workers
+            Js::invoke("setTimeout({},0)", &[function_ref.into()]);
         }
         fn drop_fn<T>(ptr: *const ()) {
             let future = unsafe { FutureRc::<T>::from_raw(ptr as *const _) };
@@ -72,6 +74,7 @@ impl Runtime {
         Self::poll(&Rc::new(RefCell::new(Box::pin(future))));
     }
 
+    // resolves with the value passed to the callback, it's owned by the caller and should be deallocated
     pub fn promise<F: FnOnce(ObjectRef) -> Vec<JsValue>>(code: &str, params_fn: F) -> FutureTask<ObjectRef> {
         let (callback_ref, future) = create_async_callback();
         Js::invoke(code, &params_fn(callback_ref));
@@ -79,10 +82,139 @@ impl Runtime {
     }
 }
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+#[derive(Debug, Default)]
+struct TokenState {
+    cancelled: bool, wakers: HashMap<usize, Waker>, next_id: usize, children: Vec<Weak<RefCell<TokenState>>>,
+    controller: Option<ObjectRef>, signal: Option<ObjectRef>, callbacks: Vec<ObjectRef>,
+}
+
+impl Drop for TokenState {
+    fn drop(&mut self) {
+        self.controller.take().into_iter().chain(self.signal.take()).for_each(Js::deallocate);
+    }
+}
+
+fn remove_callback(function_ref: ObjectRef) {
+    CALLBACK_HANDLERS.with(|s| { s.borrow_mut().remove(&function_ref); });
+    Js::deallocate(function_ref);
+}
+
+// NOTE: the `AbortController` is only created when the signal is requested and released when cancelled or dropped
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken { state: Rc<RefCell<TokenState>> }
+
+impl PartialEq for CancellationToken {
+    fn eq(&self, other: &Self) -> bool { Rc::ptr_eq(&self.state, &other.state) }
+}
+
+impl Eq for CancellationToken {}
+
+impl CancellationToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    // cancelled when the parent is cancelled
+    pub fn child(&self) -> Self {
+        let child = Self::new();
+        let mut state = self.state.borrow_mut();
+        state.children.retain(|c| c.strong_count() > 0);
+        state.children.push(Rc::downgrade(&child.state));
+        child.state.borrow_mut().cancelled = state.cancelled;
+        child
+    }
+    pub fn is_cancelled(&self) -> bool {
+        self.state.borrow().cancelled
+    }
+    pub fn cancel(&self) {
+        let (wakers, controller, signal, callbacks, children) = {
+            let mut state = self.state.borrow_mut();
+            if state.cancelled { return; }
+            state.cancelled = true;
+            (std::mem::take(&mut state.wakers), state.controller.take(), state.signal.take(), std::mem::take(&mut state.callbacks), std::mem::take(&mut state.children))
+        };
+        wakers.into_values().for_each(Waker::wake);
+        if let Some(controller) = controller {
+            Js::invoke("{}.abort()", &[controller.into()]);
+            Js::deallocate(controller);
+        }
+        signal.into_iter().for_each(Js::deallocate);
+        callbacks.into_iter().for_each(remove_callback);
+        children.iter().filter_map(Weak::upgrade).for_each(|state| CancellationToken { state }.cancel());
+    }
+    // `AbortSignal` to pass to browser APIs, eg. `fetch(url, { signal })`
+    pub fn signal(&self) -> ObjectRef {
+        let mut state = self.state.borrow_mut();
+        if let Some(signal) = state.signal { return signal; }
+        let controller = Js::invoke("return new AbortController()", &[]).to_ref().unwrap();
+        if state.cancelled {
+            Js::invoke("{}.abort()", &[controller.into()]);
+        }
+        let signal = Js::invoke("return {}.signal", &[controller.into()]).to_ref().unwrap();
+        (state.controller, state.signal) = (Some(controller), Some(signal));
+        signal
+    }
+    // like `Runtime::promise` but resolves to `Err(Cancelled)` and removes the callback when the token is cancelled
+    // NOTE: the promise should also be aborted with `signal()`, eg. `fetch({}, { signal: {} }).then({})`
+    pub async fn promise<F: FnOnce(ObjectRef) -> Vec<JsValue>>(&self, code: &str, params_fn: F) -> Result<ObjectRef, Cancelled> {
+        if self.is_cancelled() { return Err(Cancelled); }
+        let (callback_ref, future) = create_async_callback();
+        self.state.borrow_mut().callbacks.push(callback_ref);
+        Js::invoke(code, &params_fn(callback_ref));
+        let result = self.run(future).await;
+        if result.is_ok() {
+            self.state.borrow_mut().callbacks.retain(|c| *c != callback_ref);
+            remove_callback(callback_ref);
+        }
+        result
+    }
+    // resolves to `Err(Cancelled)` if the token is cancelled before the future completes
+    pub fn run<F: Future>(&self, future: F) -> Cancellable<F> {
+        let mut state = self.state.borrow_mut();
+        let id = state.next_id;
+        state.next_id += 1;
+        Cancellable { future: Box::pin(future), token: self.clone(), id }
+    }
+    pub fn cancelled(&self) -> Cancellable<std::future::Pending<()>> {
+        self.run(std::future::pending())
+    }
+}
+
+// NOTE: each future keeps only its latest waker in the token and removes it when it completes or is dropped
+pub struct Cancellable<F> { future: Pin<Box<F>>, token: CancellationToken, id: usize }
+
+impl<F: Future> Future for Cancellable<F> {
+    type Output = Result<F::Output, Cancelled>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if self.token.is_cancelled() { return Poll::Ready(Err(Cancelled)); }
+        if let Poll::Ready(value) = self.future.as_mut().poll(cx) {
+            self.token.state.borrow_mut().wakers.remove(&self.id);
+            return Poll::Ready(Ok(value));
+        }
+
+        let id = self.id;
+        let mut state = self.token.state.borrow_mut();
+        if !state.wakers.get(&id).is_some_and(|w| w.will_wake(cx.waker())) {
+            state.wakers.insert(id, cx.waker().to_owned());
+        }
+        Poll::Pending
+    }
+}
+
+impl<F> Drop for Cancellable<F> {
+    fn drop(&mut self) {
+        self.token.state.borrow_mut().wakers.remove(&self.id);
+    }
+}
 
 #[cfg(test)]
 mod tests {
 
+    use crate::callbacks::handle_callback;
+
     use super::*;
 
     #[test]
@@ -104,4 +236,78 @@ mod tests {
         assert_eq!(*has_run.borrow(), true);
     }
 
+    #[test]
+    fn test_cancellation() {
+
+        fn poll<F: Future>(future: &mut Cancellable<F>) -> Poll<Result<F::Output, Cancelled>> {
+            Pin::new(future).poll(&mut Context::from_waker(Waker::noop()))
+        }
+
+        // cancel future
+        let token = CancellationToken::new();
+        let mut future = token.run(std::future::pending::<()>());
+        assert!(poll(&mut future).is_pending());
+        token.cancel();
+        assert_eq!(poll(&mut future), Poll::Ready(Err(Cancelled)));
+
+        // cancel children
+        let token = CancellationToken::new();
+        let child = token.child();
+        token.cancel();
+        assert_eq!(child.is_cancelled(), true);
+        assert_eq!(token.child().is_cancelled(), true);
+
+        // complete before cancel
+        let token = CancellationToken::new();
+        let mut future = token.run(std::future::ready(42));
+        assert_eq!(poll(&mut future), Poll::Ready(Ok(42)));
+
+        // release wakers
+        let mut future = token.run(std::future::pending::<()>());
+        assert!(poll(&mut future).is_pending());
+        assert!(poll(&mut future).is_pending());
+        assert_eq!(token.state.borrow().wakers.len(), 1);
+        drop(future);
+        assert_eq!(token.state.borrow().wakers.len(), 0);
+    }
+
+    #[test]
+    fn test_promise() {
+
+        let has_callback = |c: ObjectRef| CALLBACK_HANDLERS.with(|s| s.borrow().contains_key(&c));
+        let last_callback = || CALLBACK_HANDLERS.with(|s| s.borrow().keys().map(|k| **k).max().unwrap());
+        let fetch = |token: &CancellationToken| {
+            let result = Rc::new(RefCell::new(None));
+            let (token, result_clone) = (token.clone(), result.clone());
+            Runtime::block_on(async move {
+                let code = "fetch('/', { signal: {} }).then({})";
+                *result_clone.borrow_mut() = Some(token.promise(code, |c| vec![token.signal().into(), c.into()]).await);
+            });
+            result
+        };
+
+        // reuse signal
+        let token = CancellationToken::new();
+        let signal = token.signal();
+        assert_eq!(token.signal(), signal);
+
+        // remove callback when resolved
+        // NOTE: the task is polled again by its wake callback
+        let result = fetch(&token);
+        let callback = token.state.borrow().callbacks[0];
+        handle_callback(*callback, 0);
+        handle_callback(last_callback(), 0);
+        assert_eq!(result.borrow().is_some_and(|r| r.is_ok()), true);
+        assert_eq!(has_callback(callback), false);
+
+        // remove callback and release signal when cancelled
+        let result = fetch(&token);
+        let callback = token.state.borrow().callbacks[0];
+        token.cancel();
+        handle_callback(last_callback(), 0);
+        assert_eq!(*result.borrow(), Some(Err(Cancelled)));
+        assert_eq!(has_callback(callback), false);
+        assert_eq!(token.state.borrow().signal, None);
+    }
+
 }
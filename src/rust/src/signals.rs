@@ -1,40 +1,770 @@
 
-use std::{cell::RefCell, rc::Rc};
+use std::{any::Any, cell::{Cell, RefCell}, collections::VecDeque, marker::PhantomData, rc::Rc};
 
-#[derive(Clone)]
-pub struct Signal<T> {
-    value: Rc<RefCell<T>>,
-    // NOTE: since `FnMut` can mutate state it has to go behind a smart pointer
-    subscribers: Rc<RefCell<Vec<Rc<RefCell<dyn FnMut() + 'static>>>>>
+// NOTE: signals, subscribers and scopes are nodes in a thread local arena and handles are
+// generational ids, so a handle to a disposed node is detected instead of pointing to freed memory
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct NodeId { index: u32, version: u32 }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Disposed;
+
+type Callback = Rc<RefCell<dyn FnMut() + 'static>>;
+
+// recomputes the value and returns whether it changed
+type Compute = Rc<dyn Fn() -> bool>;
+
+enum NodeKind { Scope, Signal, Subscriber(Callback), Memo(Compute), Effect(Callback) }
+
+// NOTE: on update, subscribers are marked `Dirty` and their dependents `Check` so nodes are
+// recomputed at most once and only after the memos they depend on are up to date
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum State { Clean, Check, Dirty }
+
+struct Node {
+    kind: NodeKind,
+    state: State,
+    value: Option<Rc<dyn Any>>,
+    owner: Option<NodeId>,
+    owned: Vec<NodeId>,
+    cleanups: Vec<Box<dyn FnOnce()>>,
+    sources: Vec<NodeId>,
+    subscribers: Vec<NodeId>,
+    contexts: Vec<Rc<dyn Any>>,
 }
 
-impl<T: Clone + Send + 'static> Signal<T> {
-    pub fn new(value: T) -> &'static Self {
-        let signal = Self { value: Rc::new(RefCell::new(value)), subscribers: Default::default(), };
-        &*Box::leak(Box::new(signal))
+#[derive(Default)]
+struct Arena { slots: Vec<(u32, Option<Node>)>, free: Vec<u32> }
+
+impl Arena {
+    fn insert(&mut self, node: Node) -> NodeId {
+        match self.free.pop() {
+            Some(index) => {
+                let slot = &mut self.slots[index as usize];
+                slot.1 = Some(node);
+                NodeId { index, version: slot.0 }
+            },
+            None => {
+                self.slots.push((0, Some(node)));
+                NodeId { index: self.slots.len() as u32 - 1, version: 0 }
+            }
+        }
     }
-    pub fn get(&self) -> T {
-        self.value.borrow().clone()
+    fn get(&self, id: NodeId) -> Option<&Node> {
+        self.slots.get(id.index as usize).filter(|s| s.0 == id.version).and_then(|s| s.1.as_ref())
+    }
+    fn get_mut(&mut self, id: NodeId) -> Option<&mut Node> {
+        self.slots.get_mut(id.index as usize).filter(|s| s.0 == id.version).and_then(|s| s.1.as_mut())
+    }
+    fn remove(&mut self, id: NodeId) -> Option<Node> {
+        let slot = self.slots.get_mut(id.index as usize).filter(|s| s.0 == id.version)?;
+        let node = slot.1.take()?;
+        slot.0 += 1;
+        self.free.push(id.index);
+        Some(node)
+    }
+}
+
+thread_local! {
+    static NODES: RefCell<Arena> = Default::default();
+    static OWNER: Cell<Option<NodeId>> = const { Cell::new(None) };
+    static OBSERVER: Cell<Option<NodeId>> = const { Cell::new(None) };
+    static PENDING: RefCell<VecDeque<NodeId>> = const { RefCell::new(VecDeque::new()) };
+    static IS_RUNNING: Cell<bool> = const { Cell::new(false) };
+    static BATCH_DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+fn create_node(kind: NodeKind, value: Option<Rc<dyn Any>>, owner: Option<NodeId>) -> NodeId {
+    NODES.with_borrow_mut(|s| {
+        let owner = owner.filter(|o| s.get(*o).is_some());
+        let state = if matches!(kind, NodeKind::Memo(_) | NodeKind::Effect(_)) { State::Dirty } else { State::Clean };
+        let node = Node { kind, state, value, owner, owned: vec![], cleanups: vec![], sources: vec![], subscribers: vec![], contexts: vec![] };
+        let id = s.insert(node);
+        if let Some(owner) = owner.and_then(|o| s.get_mut(o)) { owner.owned.push(id); }
+        id
+    })
+}
+
+fn is_alive(id: NodeId) -> bool {
+    NODES.with_borrow(|s| s.get(id).is_some())
+}
+
+fn dispose_node(id: NodeId) {
+
+    // unlink node
+    let Some(node) = NODES.with_borrow_mut(|s| {
+        let node = s.remove(id)?;
+        if let Some(owner) = node.owner.and_then(|o| s.get_mut(o)) { owner.owned.retain(|n| *n != id); }
+        for source in &node.sources {
+            if let Some(source) = s.get_mut(*source) { source.subscribers.retain(|n| *n != id); }
+        }
+        Some(node)
+    }) else { return; };
+
+    // dispose owned nodes and subscribers that can't be notified anymore
+    node.owned.iter().for_each(|n| dispose_node(*n));
+    let subscribers = NODES.with_borrow_mut(|s| {
+        node.subscribers.iter().filter(|n| {
+            let Some(subscriber) = s.get_mut(**n) else { return false; };
+            subscriber.sources.retain(|n| *n != id);
+            matches!(subscriber.kind, NodeKind::Subscriber(_))
+        }).copied().collect::<Vec<_>>()
+    });
+    subscribers.into_iter().for_each(dispose_node);
+
+    // NOTE: the value is dropped here, outside of the arena borrow
+    node.cleanups.into_iter().for_each(|f| f());
+    #[cfg(debug_assertions)]
+    inspect::forget(id);
+}
+
+// runs `f` when the node is disposed, or immediately if it's already disposed
+fn add_cleanup(id: NodeId, f: impl FnOnce() + 'static) {
+    let f = NODES.with_borrow_mut(|s| match s.get_mut(id) {
+        Some(node) => { node.cleanups.push(Box::new(f)); None },
+        None => Some(f),
+    });
+    if let Some(f) = f { f(); }
+}
+
+// runs `f` when dropped so the runtime state is restored even if a callback panics
+pub(crate) struct OnDrop<F: FnMut()>(pub F);
+
+impl<F: FnMut()> Drop for OnDrop<F> {
+    fn drop(&mut self) {
+        (self.0)();
+    }
+}
+
+fn with_observer<R>(observer: Option<NodeId>, f: impl FnOnce() -> R) -> R {
+    let previous = OBSERVER.replace(observer);
+    let _reset = OnDrop(|| OBSERVER.set(previous));
+    f()
+}
+
+// subscribes the running memo or effect to `source`
+fn track(source: NodeId) {
+    let Some(observer) = OBSERVER.get() else { return; };
+    NODES.with_borrow_mut(|s| {
+        if s.get(source).is_none() { return; }
+        let Some(node) = s.get_mut(observer) else { return; };
+        if node.sources.contains(&source) { return; }
+        node.sources.push(source);
+        s.get_mut(source).unwrap().subscribers.push(observer);
+    });
+}
+
+fn notify(source: NodeId) {
+
+    #[cfg(debug_assertions)]
+    inspect::on_update(source);
+
+    // mark subscribers and queue the ones that run callbacks
+    let callbacks = NODES.with_borrow_mut(|s| {
+        let mut callbacks = vec![];
+        let subscribers = s.get(source).map(|n| n.subscribers.clone()).unwrap_or_default();
+        let mut queue = subscribers.into_iter().map(|n| (n, State::Dirty)).collect::<VecDeque<_>>();
+        while let Some((id, state)) = queue.pop_front() {
+            let Some(node) = s.get_mut(id) else { continue; };
+            if node.state >= state { continue; }
+            let is_clean = node.state == State::Clean;
+            node.state = state;
+            if !is_clean { continue; }
+            match node.kind {
+                NodeKind::Memo(_) => queue.extend(node.subscribers.iter().map(|n| (*n, State::Check))),
+                NodeKind::Subscriber(_) | NodeKind::Effect(_) => callbacks.push(id),
+                _ => {},
+            }
+        }
+        callbacks
+    });
+    PENDING.with_borrow_mut(|s| s.extend(callbacks));
+
+    run_pending();
+}
+
+fn run_pending() {
+
+    // NOTE: updates made while running or batching are queued and run by the outer loop
+    if BATCH_DEPTH.get() > 0 || IS_RUNNING.replace(true) { return; }
+    let reset = OnDrop(|| IS_RUNNING.set(false));
+    while let Some(id) = PENDING.with_borrow_mut(|s| s.pop_front()) {
+        update_if_necessary(id);
+    }
+    drop(reset);
+    #[cfg(debug_assertions)]
+    inspect::on_flush();
+}
+
+fn node_state(id: NodeId) -> Option<State> {
+    NODES.with_borrow(|s| s.get(id).map(|n| n.state))
+}
+
+fn update_if_necessary(id: NodeId) {
+
+    // update memos this node depends on, stop when one of them changed
+    if node_state(id) == Some(State::Check) {
+        let sources = NODES.with_borrow(|s| s.get(id).map(|n| n.sources.clone()).unwrap_or_default());
+        for source in sources {
+            update_if_necessary(source);
+            if node_state(id) == Some(State::Dirty) { break; }
+        }
+    }
+
+    if node_state(id) == Some(State::Dirty) { run_node(id); }
+    NODES.with_borrow_mut(|s| { if let Some(node) = s.get_mut(id) { node.state = State::Clean; } });
+}
+
+// unlinks sources, disposes owned nodes and runs cleanups before the node reruns
+fn reset_node(id: NodeId) {
+    let Some((owned, cleanups)) = NODES.with_borrow_mut(|s| {
+        let node = s.get_mut(id)?;
+        let (owned, sources) = (std::mem::take(&mut node.owned), std::mem::take(&mut node.sources));
+        let cleanups = std::mem::take(&mut node.cleanups);
+        for source in sources {
+            if let Some(source) = s.get_mut(source) { source.subscribers.retain(|n| *n != id); }
+        }
+        Some((owned, cleanups))
+    }) else { return; };
+    owned.into_iter().for_each(dispose_node);
+    cleanups.into_iter().for_each(|f| f());
+}
+
+fn run_node(id: NodeId) {
+
+    #[cfg(debug_assertions)]
+    let _timer = inspect::Timer::start(id);
+
+    let Some(kind) = NODES.with_borrow(|s| s.get(id).map(|n| match &n.kind {
+        NodeKind::Memo(f) => Some(Ok(f.clone())),
+        NodeKind::Subscriber(f) | NodeKind::Effect(f) => Some(Err((f.clone(), matches!(n.kind, NodeKind::Effect(_))))),
+        _ => None,
+    })).flatten() else { return; };
+
+    match kind {
+        Ok(compute) => {
+
+            // recompute and mark subscribers to run when the value changed
+            reset_node(id);
+            let is_changed = Scope(id).run(|| with_observer(Some(id), &*compute));
+            if !is_changed { return; }
+            NODES.with_borrow_mut(|s| {
+                let subscribers = s.get(id).map(|n| n.subscribers.clone()).unwrap_or_default();
+                for n in subscribers {
+                    let Some(node) = s.get_mut(n) else { continue; };
+                    if node.state == State::Check { node.state = State::Dirty; }
+                }
+            });
+        },
+        Err((callback, is_effect)) => {
+
+            // NOTE: skip subscribers updating their own signal
+            let Ok(mut f) = callback.try_borrow_mut() else { return; };
+            if !is_effect { with_observer(None, &mut *f); return; }
+
+            // rerun effect with the signals it reads as new dependencies
+            reset_node(id);
+            Scope(id).run(|| with_observer(Some(id), &mut *f));
+        },
+    }
+}
+
+// runs `cb` when `source` changes
+fn subscribe(source: NodeId, cb: impl FnMut() + 'static) -> Subscription {
+
+    // store callback
+    let cb_ref: Callback = Rc::new(RefCell::new(cb));
+    let id = create_node(NodeKind::Subscriber(cb_ref.clone()), None, OWNER.get());
+    let is_linked = NODES.with_borrow_mut(|s| {
+        let Some(node) = s.get_mut(source) else { return false; };
+        node.subscribers.push(id);
+        s.get_mut(id).unwrap().sources.push(source);
+        true
+    });
+    if !is_linked { dispose_node(id); return Subscription(id); }
+
+    // trigger once
+    with_observer(None, || cb_ref.borrow_mut()());
+    Subscription(id)
+}
+
+// unsubscribes when dropped, `detach` keeps it until the scope it was created in is disposed
+#[must_use]
+#[derive(Debug, PartialEq, Eq)]
+pub struct Subscription(NodeId);
+
+impl Subscription {
+    pub fn unsubscribe(self) {}
+    pub fn is_active(&self) -> bool {
+        is_alive(self.0)
+    }
+    pub fn detach(self) {
+        std::mem::forget(self);
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        dispose_node(self.0);
+    }
+}
+
+// runs `f` now and whenever a signal or memo read in its last run changes
+pub fn create_effect(f: impl FnMut() + 'static) {
+    let id = create_node(NodeKind::Effect(Rc::new(RefCell::new(f))), None, OWNER.get());
+    update_if_necessary(id);
+}
+
+// runs `f` without subscribing the running effect or memo to the signals it reads
+pub fn untrack<R>(f: impl FnOnce() -> R) -> R {
+    with_observer(None, f)
+}
+
+// defers subscribers and effects until the outermost batch ends so they run once with the final values
+pub fn batch<R>(f: impl FnOnce() -> R) -> R {
+    BATCH_DEPTH.set(BATCH_DEPTH.get() + 1);
+    let reset = OnDrop(|| BATCH_DEPTH.set(BATCH_DEPTH.get() - 1));
+    let result = f();
+    drop(reset);
+    run_pending();
+    result
+}
+
+// runs `f` before the current effect or memo reruns or when the current scope is disposed
+pub fn on_cleanup(f: impl FnOnce() + 'static) {
+    if let Some(scope) = Scope::current() { scope.on_cleanup(f); }
+}
+
+// makes `value` available to `use_context` in the current scope and the scopes it owns
+pub fn provide_context<T: 'static>(value: T) {
+    let owner = OWNER.get().expect("Context should be provided in a scope");
+    NODES.with_borrow_mut(|s| {
+        let Some(node) = s.get_mut(owner) else { return; };
+        node.contexts.retain(|c| !c.is::<T>());
+        node.contexts.push(Rc::new(value));
+    });
+}
+
+// finds the value provided by the closest scope
+pub fn use_context<T: Clone + 'static>() -> Option<T> {
+    NODES.with_borrow(|s| {
+        let mut id = OWNER.get();
+        while let Some(node) = id.and_then(|i| s.get(i)) {
+            if let Some(value) = node.contexts.iter().find_map(|c| c.downcast_ref::<T>()) { return Some(value.clone()); }
+            id = node.owner;
+        }
+        None
+    })
+}
+
+// owns signals and subscribers created while it runs and frees them on dispose
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Scope(NodeId);
+
+impl Default for Scope {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Scope {
+    // child of the current scope
+    pub fn new() -> Self {
+        Self(create_node(NodeKind::Scope, None, OWNER.get()))
+    }
+    // disposed only explicitly
+    pub fn root() -> Self {
+        Self(create_node(NodeKind::Scope, None, None))
+    }
+    pub fn current() -> Option<Self> {
+        OWNER.get().filter(|o| is_alive(*o)).map(Self)
+    }
+    pub fn run<R>(&self, f: impl FnOnce() -> R) -> R {
+        let owner = OWNER.replace(Some(self.0));
+        let _reset = OnDrop(|| OWNER.set(owner));
+        f()
+    }
+    pub fn dispose(&self) {
+        dispose_node(self.0);
+    }
+    pub fn is_disposed(&self) -> bool {
+        !is_alive(self.0)
+    }
+    pub(crate) fn on_cleanup(&self, f: impl FnOnce() + 'static) {
+        add_cleanup(self.0, f);
+    }
+    // keeps `subscription` until this scope is disposed
+    pub(crate) fn own(&self, subscription: Subscription) {
+        self.adopt(Scope(subscription.0));
+        subscription.detach();
+    }
+    // moves `child` under this scope
+    pub(crate) fn adopt(&self, child: Scope) {
+        NODES.with_borrow_mut(|s| {
+            if s.get(self.0).is_none() { return; }
+            let Some(previous) = s.get_mut(child.0).map(|n| n.owner.replace(self.0)) else { return; };
+            if let Some(owner) = previous.and_then(|o| s.get_mut(o)) { owner.owned.retain(|n| *n != child.0); }
+            s.get_mut(self.0).unwrap().owned.push(child.0);
+        });
+    }
+}
+
+// `Copy` handle to a value owned by the scope it was created in
+pub struct Signal<T> { id: NodeId, _marker: PhantomData<fn() -> T> }
+
+impl<T> Clone for Signal<T> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<T> Copy for Signal<T> {}
+
+impl<T> std::fmt::Debug for Signal<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Signal").field("id", &self.id).finish()
+    }
+}
+
+impl<T> PartialEq for Signal<T> {
+    fn eq(&self, other: &Self) -> bool { self.id == other.id }
+}
+
+impl<T> Eq for Signal<T> {}
+
+impl<T: 'static> Signal<T> {
+    pub fn new(value: T) -> Self {
+        let value: Rc<dyn Any> = Rc::new(RefCell::new(value));
+        let id = create_node(NodeKind::Signal, Some(value), OWNER.get());
+        Self { id, _marker: PhantomData }
+    }
+    fn value(&self) -> Result<Rc<RefCell<T>>, Disposed> {
+        let value = NODES.with_borrow(|s| s.get(self.id).and_then(|n| n.value.clone())).ok_or(Disposed)?;
+        Ok(value.downcast::<RefCell<T>>().unwrap())
+    }
+    // reads the value without cloning it
+    pub fn try_with<R>(&self, f: impl FnOnce(&T) -> R) -> Result<R, Disposed> {
+        let value = self.value()?;
+        track(self.id);
+        let result = f(&value.borrow());
+        Ok(result)
+    }
+    pub fn with<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        self.try_with(f).expect("Signal is disposed")
+    }
+    // reads the value without subscribing the running effect or memo
+    pub fn with_untracked<R>(&self, f: impl FnOnce(&T) -> R) -> R {
+        untrack(|| self.with(f))
+    }
+    pub fn try_set(&self, new_value: T) -> Result<(), Disposed> {
+        self.try_update(|v| *v = new_value)
     }
     pub fn set(&self, new_value: T) {
+        self.try_set(new_value).expect("Signal is disposed")
+    }
+    // mutates the value in place and notifies subscribers
+    // NOTE: the signal can't be read inside `f`
+    pub fn try_update<R>(&self, f: impl FnOnce(&mut T) -> R) -> Result<R, Disposed> {
         // store value
-        *self.value.borrow_mut() = new_value;
+        let result = f(&mut self.value()?.borrow_mut());
 
         // trigger effects
-        self.subscribers.borrow_mut().iter().for_each(|f| { f.borrow_mut()(); });
+        notify(self.id);
+        Ok(result)
+    }
+    pub fn update<R>(&self, f: impl FnOnce(&mut T) -> R) -> R {
+        self.try_update(f).expect("Signal is disposed")
+    }
+    // runs `cb` untracked when the signal is updated
+    pub(crate) fn subscribe(&self, cb: impl FnMut() + 'static) -> Subscription {
+        subscribe(self.id, cb)
+    }
+    // runs `f` when the signal is disposed, directly or by its scope
+    pub(crate) fn on_dispose(&self, f: impl FnOnce() + 'static) {
+        add_cleanup(self.id, f);
+    }
+    pub fn dispose(&self) {
+        dispose_node(self.id);
+    }
+    pub fn is_disposed(&self) -> bool {
+        !is_alive(self.id)
+    }
+}
+
+impl<T: Clone + 'static> Signal<T> {
+    pub fn try_get(&self) -> Result<T, Disposed> {
+        self.try_with(T::clone)
+    }
+    pub fn get(&self) -> T {
+        self.try_get().expect("Signal is disposed")
+    }
+    pub fn on(&self, mut cb: impl FnMut(T) + 'static) -> Subscription {
+        let signal = *self;
+        subscribe(self.id, move || { if let Ok(v) = signal.try_get() { cb(v); } })
+    }
+}
+
+impl<T: PartialEq + 'static> Signal<T> {
+    // sets the value and notifies subscribers only if it changed, returns whether it did
+    pub fn set_if_changed(&self, new_value: T) -> bool {
+        let value = self.value().expect("Signal is disposed");
+        if *value.borrow() == new_value { return false; }
+        *value.borrow_mut() = new_value;
+        notify(self.id);
+        true
+    }
+}
+
+// derived value that tracks the signals and memos read while computing it,
+// recomputed lazily and only notifies subscribers when the value changes
+pub struct Memo<T> { id: NodeId, _marker: PhantomData<fn() -> T> }
+
+impl<T> Clone for Memo<T> {
+    fn clone(&self) -> Self { *self }
+}
+
+impl<T> Copy for Memo<T> {}
+
+impl<T> std::fmt::Debug for Memo<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Memo").field("id", &self.id).finish()
+    }
+}
+
+impl<T: Clone + PartialEq + 'static> Memo<T> {
+    pub fn new(f: impl Fn() -> T + 'static) -> Self {
+        let value = Rc::new(RefCell::new(None::<T>));
+        let value_clone = value.clone();
+        let compute = move || {
+            let new_value = f();
+            let mut value = value_clone.borrow_mut();
+            if value.as_ref() == Some(&new_value) { return false; }
+            *value = Some(new_value);
+            true
+        };
+        let id = create_node(NodeKind::Memo(Rc::new(compute)), Some(value), OWNER.get());
+        Self { id, _marker: PhantomData }
+    }
+    pub fn try_get(&self) -> Result<T, Disposed> {
+        update_if_necessary(self.id);
+        let value = NODES.with_borrow(|s| s.get(self.id).and_then(|n| n.value.clone())).ok_or(Disposed)?;
+        track(self.id);
+        let value = value.downcast::<RefCell<Option<T>>>().unwrap().borrow().clone();
+        Ok(value.unwrap())
+    }
+    pub fn get(&self) -> T {
+        self.try_get().expect("Memo is disposed")
+    }
+    pub fn on(&self, mut cb: impl FnMut(T) + 'static) -> Subscription {
+        let memo = *self;
+        subscribe(self.id, move || { if let Ok(v) = memo.try_get() { cb(v); } })
+    }
+    pub fn dispose(&self) {
+        dispose_node(self.id);
+    }
+    pub fn is_disposed(&self) -> bool {
+        !is_alive(self.id)
+    }
+}
+
+// NOTE: labels are kept so code that names nodes builds in release too, they do nothing there
+impl<T> Signal<T> {
+    pub fn label(self, name: &str) -> Self {
+        inspect::set_label(self.id, name);
+        self
+    }
+}
+
+impl<T> Memo<T> {
+    pub fn label(self, name: &str) -> Self {
+        inspect::set_label(self.id, name);
+        self
+    }
+}
+
+#[cfg(not(debug_assertions))]
+pub mod inspect {
+
+    use super::NodeId;
+
+    pub fn label(_name: &str) {}
+
+    pub(super) fn set_label(_id: NodeId, _name: &str) {}
+}
+
+// debug tools that show the signal graph and how updates propagate through it
+#[cfg(debug_assertions)]
+pub mod inspect {
+
+    use std::collections::HashMap;
+
+    use crate::invoke::{Js, JsValue};
+
+    use super::*;
+
+    const MAX_UPDATES: usize = 100;
+
+    // nodes that ran because of an update and how long each took in milliseconds
+    struct Update { sources: Vec<NodeId>, runs: Vec<(NodeId, f64)> }
+
+    thread_local! {
+        static LABELS: RefCell<HashMap<NodeId, String>> = Default::default();
+        static IS_RECORDING: Cell<bool> = const { Cell::new(false) };
+        static IS_OPEN: Cell<bool> = const { Cell::new(false) };
+        static HAS_OVERLAY: Cell<bool> = const { Cell::new(false) };
+        static UPDATES: RefCell<VecDeque<Update>> = const { RefCell::new(VecDeque::new()) };
+    }
+
+    fn now() -> f64 {
+        Js::invoke("return performance.now()", &[]).to_num().unwrap()
+    }
+
+    pub(super) struct Timer { index: usize, start: f64 }
+
+    impl Timer {
+        pub(super) fn start(id: NodeId) -> Option<Self> {
+            if !IS_OPEN.get() { return None; }
+            let index = UPDATES.with_borrow_mut(|s| {
+                let runs = &mut s.back_mut()?.runs;
+                runs.push((id, 0.0));
+                Some(runs.len() - 1)
+            })?;
+            Some(Self { index, start: now() })
+        }
+    }
+
+    impl Drop for Timer {
+        fn drop(&mut self) {
+            let duration = now() - self.start;
+            UPDATES.with_borrow_mut(|s| {
+                if let Some(run) = s.back_mut().and_then(|u| u.runs.get_mut(self.index)) { run.1 = duration; }
+            });
+        }
+    }
+
+    // NOTE: updates made while effects run or in a batch are recorded as part of the same update
+    pub(super) fn on_update(source: NodeId) {
+        if !IS_RECORDING.get() { return; }
+        UPDATES.with_borrow_mut(|s| {
+            match s.back_mut() {
+                Some(update) if IS_OPEN.get() => update.sources.push(source),
+                _ => {
+                    s.push_back(Update { sources: vec![source], runs: vec![] });
+                    if s.len() > MAX_UPDATES { s.pop_front(); }
+                },
+            }
+        });
+        IS_OPEN.set(true);
+    }
+
+    pub(super) fn on_flush() {
+        IS_OPEN.set(false);
+        if HAS_OVERLAY.get() { render_overlay(); }
+    }
+
+    pub(super) fn forget(id: NodeId) {
+        LABELS.with_borrow_mut(|s| s.remove(&id));
+    }
+
+    // records updates until `stop` is called
+    pub fn start() {
+        IS_RECORDING.set(true);
+    }
+
+    pub fn stop() {
+        IS_RECORDING.set(false);
+        IS_OPEN.set(false);
+    }
+
+    pub fn clear() {
+        UPDATES.take();
+    }
+
+    // names the running effect or memo, or the current scope
+    pub fn label(name: &str) {
+        if let Some(id) = OWNER.get() { set_label(id, name); }
+    }
+
+    pub(super) fn set_label(id: NodeId, name: &str) {
+        LABELS.with_borrow_mut(|s| s.insert(id, name.to_owned()));
+    }
+
+    fn kind_name(kind: &NodeKind) -> &'static str {
+        match kind {
+            NodeKind::Scope => "scope",
+            NodeKind::Signal => "signal",
+            NodeKind::Subscriber(_) => "subscriber",
+            NodeKind::Memo(_) => "memo",
+            NodeKind::Effect(_) => "effect",
+        }
+    }
+
+    fn name(arena: &Arena, id: NodeId) -> String {
+        let label = LABELS.with_borrow(|s| s.get(&id).cloned());
+        let kind = arena.get(id).map(|n| kind_name(&n.kind)).unwrap_or("disposed");
+        label.unwrap_or_else(|| format!("{}#{}", kind, id.index))
+    }
+
+    // signals, memos, effects and subscribers with their sources
+    fn graph(arena: &Arena) -> Vec<(NodeId, &'static str, Vec<NodeId>)> {
+        arena.slots.iter().enumerate()
+            .filter_map(|(i, (version, node))| Some((NodeId { index: i as u32, version: *version }, node.as_ref()?)))
+            .filter(|(_, n)| !matches!(n.kind, NodeKind::Scope))
+            .map(|(id, n)| (id, kind_name(&n.kind), n.sources.clone()))
+            .collect()
+    }
+
+    pub fn to_text() -> String {
+        NODES.with_borrow(|arena| {
+            let mut text = "graph\n".to_owned();
+            for (id, kind, sources) in graph(arena) {
+                let sources = sources.iter().map(|n| name(arena, *n)).collect::<Vec<_>>();
+                let sources = if sources.is_empty() { String::new() } else { format!(" <- {}", sources.join(", ")) };
+                text += &format!("  {}: {}{}\n", name(arena, id), kind, sources);
+            }
+            text += "updates\n";
+            UPDATES.with_borrow(|s| for update in s {
+                let sources = update.sources.iter().map(|n| name(arena, *n)).collect::<Vec<_>>();
+                let runs = update.runs.iter().map(|(n, t)| format!("{} {:.2}ms", name(arena, *n), t)).collect::<Vec<_>>();
+                text += &format!("  {} -> {}\n", sources.join(", "), runs.join(", "));
+            });
+            text
+        })
     }
-    pub fn on(&self, mut cb: impl FnMut(T) + 'static) {
 
-        // get callback
-        let signal_clone = self.clone();
-        let cb_ref = Rc::new(RefCell::new(move || { cb(signal_clone.get()); }));
+    // graph in the graphviz format, eg. `dot -Tsvg graph.dot`
+    pub fn to_dot() -> String {
+        NODES.with_borrow(|arena| {
+            let mut dot = "digraph signals {\n".to_owned();
+            for (id, kind, sources) in graph(arena) {
+                let shape = match kind { "signal" => "box", "memo" => "ellipse", _ => "diamond" };
+                dot += &format!("  n{} [label=\"{}\", shape={}];\n", id.index, name(arena, id).replace('"', "\\\""), shape);
+                sources.iter().for_each(|n| dot += &format!("  n{} -> n{};\n", n.index, id.index));
+            }
+            dot + "}\n"
+        })
+    }
 
-        // store callback
-        self.subscribers.borrow_mut().push(cb_ref.to_owned());
+    // shows the graph and recorded updates in a panel that refreshes after every update
+    pub fn overlay(enabled: bool) {
+        HAS_OVERLAY.set(enabled);
+        if enabled { render_overlay(); return; }
+        Js::invoke("document.getElementById('tinyweb-inspect')?.remove()", &[]);
+    }
 
-        // trigger once
-        cb_ref.borrow_mut()();
+    fn render_overlay() {
+        let code = r#"
+            let panel = document.getElementById('tinyweb-inspect');
+            if (!panel) {
+                panel = document.createElement('pre');
+                panel.id = 'tinyweb-inspect';
+                panel.style.cssText = 'position:fixed;bottom:0;right:0;max-height:50vh;overflow:auto;margin:0;padding:8px;background:#000c;color:#fff;font-size:11px;z-index:99999';
+                document.body.appendChild(panel);
+            }
+            panel.textContent = {};
+        "#;
+        Js::invoke(code, &[JsValue::Str(to_text())]);
     }
+
 }
 
 #[cfg(test)]
@@ -51,9 +781,9 @@ mod tests {
 
         // create effects
         let logs_clone = logs.clone();
-        signal.on(move |v| { logs_clone.borrow_mut().push(v); });
+        signal.on(move |v| { logs_clone.borrow_mut().push(v); }).detach();
         let logs_clone = logs.clone();
-        signal.on(move |v| { logs_clone.borrow_mut().push(v); });
+        let subscription = signal.on(move |v| { logs_clone.borrow_mut().push(v); });
 
         // update signal
         signal.set(20);
@@ -61,6 +791,197 @@ mod tests {
 
         // check logs
         assert_eq!(*logs.borrow(), vec![10, 10, 20, 20, 30, 30]);
+
+        // unsubscribe on drop
+        drop(subscription);
+        signal.set(40);
+        assert_eq!(*logs.borrow(), vec![10, 10, 20, 20, 30, 30, 40]);
+    }
+
+    #[test]
+    fn test_scope() {
+
+        // create in scope
+        let logs: Rc<RefCell<Vec<u32>>> = Default::default();
+        let global = Signal::new(0);
+        let scope = Scope::root();
+        let signal = scope.run(|| {
+            let logs_clone = logs.clone();
+            global.on(move |v| { logs_clone.borrow_mut().push(v); }).detach();
+            Signal::new(10)
+        });
+        global.set(1);
+        assert_eq!(signal.try_get(), Ok(10));
+
+        // dispose scope
+        scope.dispose();
+        global.set(2);
+        assert_eq!(*logs.borrow(), vec![0, 1]);
+        assert_eq!(signal.try_get(), Err(Disposed));
+        assert_eq!(signal.try_set(20), Err(Disposed));
+
+        // reuse slot
+        let other = Signal::new(30);
+        assert_eq!(signal.try_get(), Err(Disposed));
+        assert_eq!(other.get(), 30);
+        assert_eq!(NODES.with_borrow(|s| s.slots.iter().filter(|s| s.1.is_some()).count()), 2);
+    }
+
+    #[test]
+    fn test_effect() {
+
+        // track signals read in the last run
+        let logs: Rc<RefCell<Vec<String>>> = Default::default();
+        let logs_clone = logs.clone();
+        let show = Signal::new(true);
+        let first = Signal::new("a");
+        let second = Signal::new("b");
+        let scope = Scope::new();
+        scope.run(|| create_effect(move || {
+            let value = if show.get() { first.get() } else { second.get() };
+            logs_clone.borrow_mut().push(value.to_owned());
+            let logs_clone = logs_clone.clone();
+            on_cleanup(move || logs_clone.borrow_mut().push(format!("cleanup {}", value)));
+        }));
+        second.set("c");
+        show.set(false);
+        first.set("d");
+        assert_eq!(*logs.borrow(), vec!["a", "cleanup a", "c"]);
+
+        // cleanup on dispose
+        scope.dispose();
+        second.set("e");
+        assert_eq!(*logs.borrow(), vec!["a", "cleanup a", "c", "cleanup c"]);
+    }
+
+    #[test]
+    fn test_update() {
+
+        let logs: Rc<RefCell<Vec<usize>>> = Default::default();
+        let logs_clone = logs.clone();
+        let rows = Signal::new(Rc::new(RefCell::new(vec![1, 2])));
+        let count = Signal::new(vec![1, 2]);
+        create_effect(move || logs_clone.borrow_mut().push(count.with(|v| v.len())));
+
+        // update in place
+        count.update(|v| v.push(3));
+        assert_eq!(count.with(|v| v.iter().sum::<u32>()), 6);
+
+        // notify on change
+        assert_eq!(count.set_if_changed(vec![1, 2, 3]), false);
+        assert_eq!(count.set_if_changed(vec![1]), true);
+        assert_eq!(*logs.borrow(), vec![2, 3, 1]);
+        rows.with(|v| v.borrow_mut().clear());
+        assert_eq!(rows.get().borrow().len(), 0);
+    }
+
+    #[test]
+    fn test_batch() {
+
+        let logs: Rc<RefCell<Vec<(u32, u32)>>> = Default::default();
+        let logs_clone = logs.clone();
+        let first = Signal::new(0);
+        let second = Signal::new(0);
+        create_effect(move || logs_clone.borrow_mut().push((first.get(), second.get())));
+
+        // run once with final values
+        batch(|| {
+            first.set(1);
+            batch(|| second.set(1));
+            second.set(2);
+            assert_eq!(logs.borrow().len(), 1);
+        });
+        assert_eq!(*logs.borrow(), vec![(0, 0), (1, 2)]);
+    }
+
+    #[test]
+    fn test_panic() {
+
+        let logs: Rc<RefCell<Vec<u32>>> = Default::default();
+        let logs_clone = logs.clone();
+        let signal = Signal::new(0);
+        let _subscription = signal.on(move |v| logs_clone.borrow_mut().push(v));
+
+        // notify after a batch panics
+        let _ = std::panic::catch_unwind(|| batch(|| panic!("batch")));
+        signal.set(1);
+
+        // notify after an effect panics
+        create_effect(move || if signal.get() == 2 { panic!("effect") });
+        let _ = std::panic::catch_unwind(|| signal.set(2));
+        signal.set(3);
+        assert_eq!(*logs.borrow(), vec![0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn test_context() {
+
+        // read from ancestors
+        let scope = Scope::root();
+        let (outer, inner, sibling) = scope.run(|| {
+            provide_context("light");
+            let inner = Scope::new().run(|| {
+                provide_context("dark");
+                Scope::new().run(use_context::<&str>)
+            });
+            (use_context::<&str>(), inner, Scope::new().run(use_context::<&str>))
+        });
+        assert_eq!((outer, inner, sibling), (Some("light"), Some("dark"), Some("light")));
+
+        // isolate roots
+        assert_eq!(Scope::root().run(use_context::<&str>), None);
+        assert_eq!(use_context::<u32>(), None);
+    }
+
+    #[test]
+    fn test_inspect() {
+
+        // record update path
+        inspect::start();
+        let count = Signal::new(1).label("count");
+        let double = Memo::new(move || count.get() * 2).label("double");
+        create_effect(move || { inspect::label("render"); double.get(); });
+        count.set(2);
+        inspect::stop();
+
+        // dump graph
+        let text = inspect::to_text();
+        assert!(text.contains("  double: memo <- count\n"));
+        assert!(text.contains("  render: effect <- double\n"));
+        assert!(text.contains("  count -> double 0.00ms, render 0.00ms\n"));
+        assert!(inspect::to_dot().contains(&format!("n{} -> n{};", count.id.index, double.id.index)));
+    }
+
+    #[test]
+    fn test_memo() {
+
+        // diamond dependencies
+        let evaluations = Rc::new(RefCell::new(0));
+        let evaluations_clone = evaluations.clone();
+        let signal = Signal::new(1);
+        let double = Memo::new(move || signal.get() * 2);
+        let triple = Memo::new(move || signal.get() * 3);
+        let sum = Memo::new(move || { *evaluations_clone.borrow_mut() += 1; double.get() + triple.get() });
+        assert_eq!(*evaluations.borrow(), 0);
+
+        // evaluate once per update
+        let logs: Rc<RefCell<Vec<u32>>> = Default::default();
+        let logs_clone = logs.clone();
+        let _subscription = sum.on(move |v| { logs_clone.borrow_mut().push(v); });
+        signal.set(2);
+        assert_eq!(*logs.borrow(), vec![5, 10]);
+        assert_eq!(*evaluations.borrow(), 2);
+
+        // notify only on change
+        let is_even = Memo::new(move || signal.get().is_multiple_of(2));
+        let logs: Rc<RefCell<Vec<bool>>> = Default::default();
+        let logs_clone = logs.clone();
+        let _subscription = is_even.on(move |v| { logs_clone.borrow_mut().push(v); });
+        signal.set(4);
+        signal.set(5);
+        assert_eq!(*logs.borrow(), vec![true, false]);
+        assert_eq!(sum.get(), 25);
+        assert_eq!(*evaluations.borrow(), 4);
     }
 
 }
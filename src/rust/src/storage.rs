@@ -0,0 +1,146 @@
+
+use std::cell::Cell;
+
+use crate::invoke::{Js, JsValue};
+use crate::signals::Signal;
+
+// values are stored as strings, eg. in `localStorage` or messages to other tabs
+pub trait Codec: Sized {
+    fn encode(&self) -> String;
+    fn decode(value: &str) -> Result<Self, String>;
+}
+
+impl Codec for String {
+    fn encode(&self) -> String { self.to_owned() }
+    fn decode(value: &str) -> Result<Self, String> { Ok(value.to_owned()) }
+}
+
+macro_rules! impl_codec {
+    ($($t:ty),*) => {
+        $(impl Codec for $t {
+            fn encode(&self) -> String { self.to_string() }
+            fn decode(value: &str) -> Result<Self, String> { value.parse().map_err(|_| format!("Invalid value: {}", value)) }
+        })*
+    };
+}
+
+impl_codec!(bool, i32, u32, i64, u64, usize, f32, f64);
+
+// where persisted values are kept, eg. `Storage::Local`
+pub trait StorageBackend {
+    fn get(&self, key: &str) -> Option<String>;
+    // returns false when the value can't be stored, eg. when the quota is exceeded
+    fn set(&self, key: &str, value: &str) -> bool;
+    fn remove(&self, key: &str);
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Storage { Local, Session }
+
+impl Storage {
+    fn name(&self) -> &'static str {
+        match self { Self::Local => "localStorage", Self::Session => "sessionStorage" }
+    }
+}
+
+// NOTE: storage can be unavailable, eg. in private browsing or when blocked by the user
+impl StorageBackend for Storage {
+    fn get(&self, key: &str) -> Option<String> {
+        let code = format!("try {{ return window.{}.getItem({{}}) ?? undefined }} catch (e) {{ }}", self.name());
+        match Js::invoke(&code, &[key.into()]) { JsValue::Str(s) => Some(s), _ => None }
+    }
+    fn set(&self, key: &str, value: &str) -> bool {
+        let code = format!("try {{ window.{}.setItem({{}},{{}}); return true }} catch (e) {{ return false }}", self.name());
+        matches!(Js::invoke(&code, &[key.into(), value.into()]), JsValue::Bool(true))
+    }
+    fn remove(&self, key: &str) {
+        let code = format!("try {{ window.{}.removeItem({{}}) }} catch (e) {{ }}", self.name());
+        Js::invoke(&code, &[key.into()]);
+    }
+}
+
+impl<T: Codec + 'static> Signal<T> {
+    // signal initialised from `localStorage` that stores its value on every update
+    pub fn persisted(key: &str, default: T) -> Self {
+        Self::persisted_in(Storage::Local, key, default)
+    }
+    // NOTE: missing or corrupt values fall back to `default` and failed writes keep the value in memory only
+    pub fn persisted_in(storage: impl StorageBackend + 'static, key: &str, default: T) -> Self {
+        let value = storage.get(key).and_then(|v| T::decode(&v).ok()).unwrap_or(default);
+        let signal = Signal::new(value);
+
+        // write on update
+        let (key, is_first) = (key.to_owned(), Cell::new(true));
+        signal.subscribe(move || {
+            if is_first.replace(false) { return; }
+            let Ok(value) = signal.try_with(|v| v.encode()) else { return; };
+            storage.set(&key, &value);
+        }).detach();
+
+        signal
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::cell::RefCell;
+    use std::collections::HashMap;
+    use std::rc::Rc;
+
+    use super::*;
+
+    #[test]
+    fn test_codec() {
+        assert_eq!(true.encode(), "true");
+        assert_eq!(bool::decode("false"), Ok(false));
+        assert_eq!(u32::decode("42"), Ok(42));
+        assert!(u32::decode("-1").is_err());
+        assert_eq!(String::decode("a"), Ok("a".to_owned()));
+    }
+
+    #[derive(Clone, Default)]
+    struct MemoryStorage(Rc<RefCell<HashMap<String, String>>>);
+
+    impl StorageBackend for MemoryStorage {
+        fn get(&self, key: &str) -> Option<String> { self.0.borrow().get(key).cloned() }
+        fn set(&self, key: &str, value: &str) -> bool { self.0.borrow_mut().insert(key.to_owned(), value.to_owned()); true }
+        fn remove(&self, key: &str) { self.0.borrow_mut().remove(key); }
+    }
+
+    #[test]
+    fn test_persisted() {
+
+        // use default when missing and write on update
+        let storage = MemoryStorage::default();
+        let signal = Signal::persisted_in(storage.clone(), "dark-mode", false);
+        assert_eq!(signal.get(), false);
+        assert_eq!(storage.get("dark-mode"), None);
+        signal.set(true);
+        assert_eq!(storage.get("dark-mode"), Some("true".to_owned()));
+
+        // read stored value
+        assert_eq!(Signal::persisted_in(storage.clone(), "dark-mode", false).get(), true);
+        assert_eq!(Signal::persisted_in(MemoryStorage::default(), "dark-mode", false).get(), false);
+
+        // use default when corrupt
+        storage.set("count", "invalid");
+        assert_eq!(Signal::persisted_in(storage.clone(), "count", 5).get(), 5);
+        storage.remove("count");
+        assert_eq!(storage.get("count"), None);
+    }
+
+    #[test]
+    fn test_storage() {
+
+        // NOTE: invokes do nothing outside the browser so storage behaves as unavailable
+        assert_eq!(Storage::Local.get("dark-mode"), None);
+        assert_eq!(Storage::Session.set("dark-mode", "true"), false);
+
+        // keep value in memory
+        let signal = Signal::persisted("dark-mode", false);
+        signal.set(true);
+        assert_eq!(signal.get(), true);
+    }
+
+}
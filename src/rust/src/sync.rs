@@ -0,0 +1,715 @@
+
+use std::{
+    cell::RefCell,
+    collections::VecDeque,
+    future::Future,
+    pin::Pin,
+    rc::{Rc, Weak},
+    task::{Context, Poll, Waker}
+};
+
+// NOTE: channels are single threaded (`Rc` based) and wake the task that polls them,
+// which is the `Runtime` waker when running inside `Runtime::block_on`
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RecvError;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SendError<T>(pub T);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TryRecvError { Empty, Closed }
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrySendError<T> { Full(T), Closed(T) }
+
+fn wake(waker: &mut Option<Waker>) {
+    if let Some(waker) = waker.take() { waker.wake(); }
+}
+
+fn wake_all(wakers: &mut Vec<Waker>) {
+    wakers.drain(..).for_each(Waker::wake);
+}
+
+pub mod oneshot {
+
+    use super::*;
+
+    struct Inner<T> { value: Option<T>, waker: Option<Waker>, tx_closed: bool, rx_closed: bool }
+
+    pub struct Sender<T> { inner: Rc<RefCell<Inner<T>>> }
+    pub struct Receiver<T> { inner: Rc<RefCell<Inner<T>>> }
+
+    pub fn channel<T>() -> (Sender<T>, Receiver<T>) {
+        let inner = Inner { value: None, waker: None, tx_closed: false, rx_closed: false };
+        let inner = Rc::new(RefCell::new(inner));
+        (Sender { inner: inner.clone() }, Receiver { inner })
+    }
+
+    impl<T> Sender<T> {
+        pub fn send(self, value: T) -> Result<(), SendError<T>> {
+            let mut inner = self.inner.borrow_mut();
+            if inner.rx_closed { return Err(SendError(value)); }
+            inner.value = Some(value);
+            Ok(())
+        }
+        pub fn is_closed(&self) -> bool {
+            self.inner.borrow().rx_closed
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            let mut inner = self.inner.borrow_mut();
+            inner.tx_closed = true;
+            wake(&mut inner.waker);
+        }
+    }
+
+    impl<T> Receiver<T> {
+        pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+            let mut inner = self.inner.borrow_mut();
+            match inner.value.take() {
+                Some(value) => Ok(value),
+                None if inner.tx_closed => Err(TryRecvError::Closed),
+                None => Err(TryRecvError::Empty),
+            }
+        }
+        pub fn close(&mut self) {
+            self.inner.borrow_mut().rx_closed = true;
+        }
+    }
+
+    impl<T> Drop for Receiver<T> {
+        fn drop(&mut self) {
+            self.close();
+        }
+    }
+
+    impl<T> Future for Receiver<T> {
+        type Output = Result<T, RecvError>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let this = self.get_mut();
+            match this.try_recv() {
+                Ok(value) => Poll::Ready(Ok(value)),
+                Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError)),
+                Err(TryRecvError::Empty) => {
+                    this.inner.borrow_mut().waker = Some(cx.waker().to_owned());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+}
+
+pub mod mpsc {
+
+    use super::*;
+
+    struct Inner<T> {
+        queue: VecDeque<T>,
+        capacity: Option<usize>,
+        rx_waker: Option<Waker>,
+        tx_wakers: Vec<Waker>,
+        senders: usize,
+        rx_closed: bool,
+    }
+
+    pub struct Sender<T> { inner: Rc<RefCell<Inner<T>>> }
+    pub struct Receiver<T> { inner: Rc<RefCell<Inner<T>>> }
+
+    fn channel<T>(capacity: Option<usize>) -> (Sender<T>, Receiver<T>) {
+        let inner = Inner { queue: VecDeque::new(), capacity, rx_waker: None, tx_wakers: vec![], senders: 1, rx_closed: false };
+        let inner = Rc::new(RefCell::new(inner));
+        (Sender { inner: inner.clone() }, Receiver { inner })
+    }
+
+    pub fn unbounded<T>() -> (Sender<T>, Receiver<T>) {
+        channel(None)
+    }
+
+    pub fn bounded<T>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+        assert!(capacity > 0, "Capacity should be greater than zero");
+        channel(Some(capacity))
+    }
+
+    impl<T> Sender<T> {
+        pub fn try_send(&self, value: T) -> Result<(), TrySendError<T>> {
+            let mut inner = self.inner.borrow_mut();
+            if inner.rx_closed { return Err(TrySendError::Closed(value)); }
+            if inner.capacity.is_some_and(|c| inner.queue.len() >= c) { return Err(TrySendError::Full(value)); }
+            inner.queue.push_back(value);
+            wake(&mut inner.rx_waker);
+            Ok(())
+        }
+        pub fn send(&self, value: T) -> Send<'_, T> {
+            Send { sender: self, value: Some(value) }
+        }
+        pub fn is_closed(&self) -> bool {
+            self.inner.borrow().rx_closed
+        }
+    }
+
+    impl<T> Clone for Sender<T> {
+        fn clone(&self) -> Self {
+            self.inner.borrow_mut().senders += 1;
+            Self { inner: self.inner.clone() }
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            let mut inner = self.inner.borrow_mut();
+            inner.senders -= 1;
+            if inner.senders == 0 { wake(&mut inner.rx_waker); }
+        }
+    }
+
+    pub struct Send<'a, T> { sender: &'a Sender<T>, value: Option<T> }
+
+    impl<T> Unpin for Send<'_, T> {}
+
+    impl<T> Future for Send<'_, T> {
+        type Output = Result<(), SendError<T>>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let this = self.get_mut();
+            let value = this.value.take().expect("Polled after completion");
+            match this.sender.try_send(value) {
+                Ok(()) => Poll::Ready(Ok(())),
+                Err(TrySendError::Closed(value)) => Poll::Ready(Err(SendError(value))),
+                Err(TrySendError::Full(value)) => {
+                    this.value = Some(value);
+                    this.sender.inner.borrow_mut().tx_wakers.push(cx.waker().to_owned());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+
+    impl<T> Receiver<T> {
+        pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+            let mut inner = self.inner.borrow_mut();
+            match inner.queue.pop_front() {
+                Some(value) => {
+                    wake_all(&mut inner.tx_wakers);
+                    Ok(value)
+                },
+                None if inner.senders == 0 => Err(TryRecvError::Closed),
+                None => Err(TryRecvError::Empty),
+            }
+        }
+        pub fn poll_recv(&mut self, cx: &mut Context<'_>) -> Poll<Option<T>> {
+            match self.try_recv() {
+                Ok(value) => Poll::Ready(Some(value)),
+                Err(TryRecvError::Closed) => Poll::Ready(None),
+                Err(TryRecvError::Empty) => {
+                    self.inner.borrow_mut().rx_waker = Some(cx.waker().to_owned());
+                    Poll::Pending
+                }
+            }
+        }
+        pub fn recv(&mut self) -> Recv<'_, T> {
+            Recv { receiver: self }
+        }
+        pub fn close(&mut self) {
+            let mut inner = self.inner.borrow_mut();
+            inner.rx_closed = true;
+            wake_all(&mut inner.tx_wakers);
+        }
+    }
+
+    impl<T> Drop for Receiver<T> {
+        fn drop(&mut self) {
+            self.close();
+        }
+    }
+
+    pub struct Recv<'a, T> { receiver: &'a mut Receiver<T> }
+
+    impl<T> Future for Recv<'_, T> {
+        type Output = Option<T>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            self.get_mut().receiver.poll_recv(cx)
+        }
+    }
+}
+
+pub mod broadcast {
+
+    use super::*;
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum RecvError { Closed, Lagged(u64) }
+
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub enum TryRecvError { Empty, Closed, Lagged(u64) }
+
+    struct Queue<T> { values: VecDeque<T>, lagged: u64, waker: Option<Waker> }
+
+    struct Inner<T> { capacity: usize, receivers: Vec<Weak<RefCell<Queue<T>>>>, senders: usize }
+
+    pub struct Sender<T> { inner: Rc<RefCell<Inner<T>>> }
+    pub struct Receiver<T> { inner: Rc<RefCell<Inner<T>>>, queue: Rc<RefCell<Queue<T>>> }
+
+    pub fn channel<T: Clone>(capacity: usize) -> (Sender<T>, Receiver<T>) {
+        assert!(capacity > 0, "Capacity should be greater than zero");
+        let inner = Rc::new(RefCell::new(Inner { capacity, receivers: vec![], senders: 1 }));
+        let receiver = subscribe(&inner);
+        (Sender { inner }, receiver)
+    }
+
+    fn subscribe<T>(inner: &Rc<RefCell<Inner<T>>>) -> Receiver<T> {
+        let queue = Rc::new(RefCell::new(Queue { values: VecDeque::new(), lagged: 0, waker: None }));
+        inner.borrow_mut().receivers.push(Rc::downgrade(&queue));
+        Receiver { inner: inner.clone(), queue }
+    }
+
+    impl<T: Clone> Sender<T> {
+        // returns the number of receivers the value was sent to
+        pub fn send(&self, value: T) -> Result<usize, SendError<T>> {
+            let mut inner = self.inner.borrow_mut();
+            inner.receivers.retain(|r| r.strong_count() > 0);
+            if inner.receivers.is_empty() { return Err(SendError(value)); }
+
+            // drop the oldest value when a receiver is full
+            let capacity = inner.capacity;
+            for receiver in inner.receivers.iter().filter_map(Weak::upgrade) {
+                let mut queue = receiver.borrow_mut();
+                if queue.values.len() >= capacity {
+                    queue.values.pop_front();
+                    queue.lagged += 1;
+                }
+                queue.values.push_back(value.clone());
+                wake(&mut queue.waker);
+            }
+            Ok(inner.receivers.len())
+        }
+    }
+
+    impl<T> Sender<T> {
+        pub fn subscribe(&self) -> Receiver<T> {
+            subscribe(&self.inner)
+        }
+        pub fn receiver_count(&self) -> usize {
+            self.inner.borrow().receivers.iter().filter(|r| r.strong_count() > 0).count()
+        }
+    }
+
+    impl<T> Clone for Sender<T> {
+        fn clone(&self) -> Self {
+            self.inner.borrow_mut().senders += 1;
+            Self { inner: self.inner.clone() }
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            let mut inner = self.inner.borrow_mut();
+            inner.senders -= 1;
+            if inner.senders > 0 { return; }
+            inner.receivers.iter().filter_map(Weak::upgrade).for_each(|r| wake(&mut r.borrow_mut().waker));
+        }
+    }
+
+    impl<T> Receiver<T> {
+        pub fn try_recv(&mut self) -> Result<T, TryRecvError> {
+            let mut queue = self.queue.borrow_mut();
+            if queue.lagged > 0 {
+                return Err(TryRecvError::Lagged(std::mem::take(&mut queue.lagged)));
+            }
+            match queue.values.pop_front() {
+                Some(value) => Ok(value),
+                None if self.inner.borrow().senders == 0 => Err(TryRecvError::Closed),
+                None => Err(TryRecvError::Empty),
+            }
+        }
+        pub fn recv(&mut self) -> Recv<'_, T> {
+            Recv { receiver: self }
+        }
+        // new receiver that only gets values sent from now on
+        pub fn resubscribe(&self) -> Self {
+            subscribe(&self.inner)
+        }
+    }
+
+    pub struct Recv<'a, T> { receiver: &'a mut Receiver<T> }
+
+    impl<T> Future for Recv<'_, T> {
+        type Output = Result<T, RecvError>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let receiver = &mut self.get_mut().receiver;
+            match receiver.try_recv() {
+                Ok(value) => Poll::Ready(Ok(value)),
+                Err(TryRecvError::Closed) => Poll::Ready(Err(RecvError::Closed)),
+                Err(TryRecvError::Lagged(n)) => Poll::Ready(Err(RecvError::Lagged(n))),
+                Err(TryRecvError::Empty) => {
+                    receiver.queue.borrow_mut().waker = Some(cx.waker().to_owned());
+                    Poll::Pending
+                }
+            }
+        }
+    }
+}
+
+pub mod watch {
+
+    use super::*;
+
+    struct Inner<T> { value: T, version: u64, wakers: Vec<Waker>, tx_closed: bool }
+
+    pub struct Sender<T> { inner: Rc<RefCell<Inner<T>>> }
+    pub struct Receiver<T> { inner: Rc<RefCell<Inner<T>>>, version: u64 }
+
+    pub fn channel<T>(value: T) -> (Sender<T>, Receiver<T>) {
+        let inner = Rc::new(RefCell::new(Inner { value, version: 0, wakers: vec![], tx_closed: false }));
+        (Sender { inner: inner.clone() }, Receiver { inner, version: 0 })
+    }
+
+    impl<T> Sender<T> {
+        pub fn send(&self, value: T) {
+            self.send_modify(|v| *v = value);
+        }
+        pub fn send_modify(&self, f: impl FnOnce(&mut T)) {
+            let mut inner = self.inner.borrow_mut();
+            f(&mut inner.value);
+            inner.version += 1;
+            wake_all(&mut inner.wakers);
+        }
+        pub fn subscribe(&self) -> Receiver<T> {
+            let version = self.inner.borrow().version;
+            Receiver { inner: self.inner.clone(), version }
+        }
+    }
+
+    impl<T> Drop for Sender<T> {
+        fn drop(&mut self) {
+            let mut inner = self.inner.borrow_mut();
+            inner.tx_closed = true;
+            wake_all(&mut inner.wakers);
+        }
+    }
+
+    impl<T: Clone> Receiver<T> {
+        // marks the current value as seen
+        pub fn get(&mut self) -> T {
+            let inner = self.inner.borrow();
+            self.version = inner.version;
+            inner.value.clone()
+        }
+    }
+
+    impl<T> Receiver<T> {
+        pub fn has_changed(&self) -> bool {
+            self.inner.borrow().version != self.version
+        }
+        pub fn changed(&mut self) -> Changed<'_, T> {
+            Changed { receiver: self }
+        }
+    }
+
+    impl<T> Clone for Receiver<T> {
+        fn clone(&self) -> Self {
+            Self { inner: self.inner.clone(), version: self.version }
+        }
+    }
+
+    pub struct Changed<'a, T> { receiver: &'a mut Receiver<T> }
+
+    impl<T> Future for Changed<'_, T> {
+        type Output = Result<(), RecvError>;
+
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+            let receiver = &mut self.get_mut().receiver;
+            let mut inner = receiver.inner.borrow_mut();
+            if inner.version != receiver.version {
+                receiver.version = inner.version;
+                Poll::Ready(Ok(()))
+            } else if inner.tx_closed {
+                Poll::Ready(Err(RecvError))
+            } else {
+                inner.wakers.push(cx.waker().to_owned());
+                Poll::Pending
+            }
+        }
+    }
+}
+
+// NOTE: poll-next style trait, similar to `futures::Stream`
+pub trait Stream {
+    type Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>>;
+
+    fn next(&mut self) -> Next<'_, Self> where Self: Unpin {
+        Next { stream: self }
+    }
+}
+
+pub struct Next<'a, S: ?Sized> { stream: &'a mut S }
+
+impl<S: Stream + Unpin + ?Sized> Future for Next<'_, S> {
+    type Output = Option<S::Item>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        Pin::new(&mut *self.get_mut().stream).poll_next(cx)
+    }
+}
+
+impl<T> Stream for mpsc::Receiver<T> {
+    type Item = T;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.get_mut().poll_recv(cx)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Either<A, B> { Left(A), Right(B) }
+
+enum MaybeDone<F: Future> { Pending(Pin<Box<F>>), Done(Option<F::Output>) }
+
+impl<F: Future> MaybeDone<F> {
+    fn new(future: F) -> Self {
+        Self::Pending(Box::pin(future))
+    }
+    // returns true when the future has completed
+    fn poll(&mut self, cx: &mut Context<'_>) -> bool {
+        if let MaybeDone::Pending(future) = self {
+            let Poll::Ready(value) = future.as_mut().poll(cx) else { return false; };
+            *self = MaybeDone::Done(Some(value));
+        }
+        true
+    }
+    fn take(&mut self) -> F::Output {
+        match self {
+            MaybeDone::Done(value) => value.take().expect("Polled after completion"),
+            MaybeDone::Pending(_) => unreachable!(),
+        }
+    }
+}
+
+pub struct Join<A: Future, B: Future> { a: MaybeDone<A>, b: MaybeDone<B> }
+
+// NOTE: futures are pinned in their own boxes so the combinators are never structurally pinned
+impl<A: Future, B: Future> Unpin for Join<A, B> {}
+
+impl<A: Future, B: Future> Future for Join<A, B> {
+    type Output = (A::Output, B::Output);
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let (a, b) = (this.a.poll(cx), this.b.poll(cx));
+        if a && b { Poll::Ready((this.a.take(), this.b.take())) } else { Poll::Pending }
+    }
+}
+
+pub struct JoinAll<F: Future> { futures: Vec<MaybeDone<F>> }
+
+impl<F: Future> Unpin for JoinAll<F> {}
+
+impl<F: Future> Future for JoinAll<F> {
+    type Output = Vec<F::Output>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.get_mut();
+        let pending = this.futures.iter_mut().map(|f| f.poll(cx)).filter(|done| !done).count();
+        if pending == 0 { Poll::Ready(this.futures.iter_mut().map(MaybeDone::take).collect()) } else { Poll::Pending }
+    }
+}
+
+pub struct Select<A, B> { a: Pin<Box<A>>, b: Pin<Box<B>> }
+
+impl<A: Future, B: Future> Future for Select<A, B> {
+    type Output = Either<A::Output, B::Output>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        if let Poll::Ready(value) = self.a.as_mut().poll(cx) { return Poll::Ready(Either::Left(value)); }
+        if let Poll::Ready(value) = self.b.as_mut().poll(cx) { return Poll::Ready(Either::Right(value)); }
+        Poll::Pending
+    }
+}
+
+pub struct Race<F> { futures: Vec<Pin<Box<F>>> }
+
+impl<F: Future> Future for Race<F> {
+    type Output = (usize, F::Output);
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        for (i, future) in self.futures.iter_mut().enumerate() {
+            if let Poll::Ready(value) = future.as_mut().poll(cx) { return Poll::Ready((i, value)); }
+        }
+        Poll::Pending
+    }
+}
+
+// waits for both futures
+pub fn join<A: Future, B: Future>(a: A, b: B) -> Join<A, B> {
+    Join { a: MaybeDone::new(a), b: MaybeDone::new(b) }
+}
+
+// waits for all futures and returns their outputs in order
+pub fn join_all<F: Future>(futures: impl IntoIterator<Item = F>) -> JoinAll<F> {
+    JoinAll { futures: futures.into_iter().map(MaybeDone::new).collect() }
+}
+
+// waits for the first future to complete and drops the other
+pub fn select<A: Future, B: Future>(a: A, b: B) -> Select<A, B> {
+    Select { a: Box::pin(a), b: Box::pin(b) }
+}
+
+// waits for the first future to complete and returns its index and output
+pub fn race<F: Future>(futures: impl IntoIterator<Item = F>) -> Race<F> {
+    let futures = futures.into_iter().map(Box::pin).collect::<Vec<_>>();
+    assert!(!futures.is_empty(), "Race needs at least one future");
+    Race { futures }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::future::poll_fn;
+    use std::pin::pin;
+
+    use super::*;
+
+    // polls until ready, `Waker::noop` is enough because channels are polled again in the loop
+    fn run<F: Future>(future: F) -> F::Output {
+        let mut future = pin!(future);
+        let mut context = Context::from_waker(Waker::noop());
+        for _ in 0..1_000 {
+            if let Poll::Ready(value) = future.as_mut().poll(&mut context) { return value; }
+        }
+        panic!("Future did not complete");
+    }
+
+    fn is_pending<F: Future>(future: F) -> bool {
+        let mut context = Context::from_waker(Waker::noop());
+        pin!(future).poll(&mut context).is_pending()
+    }
+
+    #[test]
+    fn test_oneshot() {
+
+        // send value
+        let (tx, rx) = oneshot::channel();
+        tx.send(42).unwrap();
+        assert_eq!(run(rx), Ok(42));
+
+        // drop sender
+        let (tx, rx) = oneshot::channel::<u32>();
+        drop(tx);
+        assert_eq!(run(rx), Err(RecvError));
+
+        // drop receiver
+        let (tx, rx) = oneshot::channel();
+        drop(rx);
+        assert_eq!(tx.send(42), Err(SendError(42)));
+
+        // runtime
+        let (tx, rx) = oneshot::channel();
+        let has_run = Rc::new(RefCell::new(false));
+        let has_run_clone = has_run.clone();
+        tx.send(true).unwrap();
+        crate::runtime::Runtime::block_on(async move { *has_run_clone.borrow_mut() = rx.await.unwrap(); });
+        assert_eq!(*has_run.borrow(), true);
+    }
+
+    #[test]
+    fn test_mpsc() {
+
+        // unbounded
+        let (tx, mut rx) = mpsc::unbounded();
+        let tx_clone = tx.clone();
+        tx.try_send(1).unwrap();
+        tx_clone.try_send(2).unwrap();
+        drop((tx, tx_clone));
+        assert_eq!(run(rx.recv()), Some(1));
+        assert_eq!(run(rx.next()), Some(2));
+        assert_eq!(run(rx.next()), None);
+
+        // bounded
+        let (tx, mut rx) = mpsc::bounded(1);
+        tx.try_send(1).unwrap();
+        assert_eq!(tx.try_send(2), Err(TrySendError::Full(2)));
+        assert!(is_pending(tx.send(2)));
+        let (sent, received) = run(join(tx.send(2), async { (rx.recv().await, rx.recv().await) }));
+        assert_eq!(sent, Ok(()));
+        assert_eq!(received, (Some(1), Some(2)));
+
+        // closed
+        rx.close();
+        assert_eq!(tx.try_send(3), Err(TrySendError::Closed(3)));
+    }
+
+    #[test]
+    fn test_broadcast() {
+
+        // send to all receivers
+        let (tx, mut rx1) = broadcast::channel(2);
+        let mut rx2 = tx.subscribe();
+        assert_eq!(tx.send(1), Ok(2));
+        assert_eq!(run(rx1.recv()), Ok(1));
+        assert_eq!(run(rx2.recv()), Ok(1));
+        assert!(is_pending(rx1.recv()));
+
+        // lagged
+        (2..=4).for_each(|i| { tx.send(i).unwrap(); });
+        assert_eq!(run(rx1.recv()), Err(broadcast::RecvError::Lagged(1)));
+        assert_eq!(run(rx1.recv()), Ok(3));
+        assert_eq!(rx1.try_recv(), Ok(4));
+        assert_eq!(rx1.try_recv(), Err(broadcast::TryRecvError::Empty));
+
+        // closed
+        drop(tx);
+        assert_eq!(run(rx1.recv()), Err(broadcast::RecvError::Closed));
+        assert_eq!(run(rx2.recv()), Err(broadcast::RecvError::Lagged(1)));
+    }
+
+    #[test]
+    fn test_watch() {
+
+        let (tx, mut rx) = watch::channel(0);
+        assert!(!rx.has_changed());
+        assert!(is_pending(rx.changed()));
+
+        // keeps last value
+        tx.send(1);
+        tx.send_modify(|v| *v += 1);
+        assert_eq!(run(rx.changed()), Ok(()));
+        assert_eq!(rx.get(), 2);
+        assert!(is_pending(rx.changed()));
+
+        // closed
+        drop(tx);
+        assert_eq!(run(rx.changed()), Err(RecvError));
+    }
+
+    #[test]
+    fn test_combinators() {
+
+        // join
+        let (tx, rx) = oneshot::channel();
+        let (a, b) = run(join(rx, async move { tx.send(1).unwrap(); 2 }));
+        assert_eq!((a, b), (Ok(1), 2));
+
+        // join all
+        let values = run(join_all((0..3).map(|i| async move { i * 2 })));
+        assert_eq!(values, vec![0, 2, 4]);
+
+        // select
+        let (_tx, rx) = oneshot::channel::<u32>();
+        let value = run(select(rx, async { "ready" }));
+        assert_eq!(value, Either::Right("ready"));
+
+        // race
+        let mut polls = 0;
+        let pending = poll_fn(move |_| { polls += 1; if polls > 2 { Poll::Ready(0u32) } else { Poll::Pending } });
+        let (index, value) = run(race([Box::pin(pending) as Pin<Box<dyn Future<Output = u32>>>, Box::pin(async { 1 })]));
+        assert_eq!((index, value), (1, 1));
+    }
+
+}
@@ -0,0 +1,200 @@
+
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+
+use crate::callbacks::{create_callback, CALLBACK_HANDLERS};
+use crate::invoke::{Js, JsValue, ObjectRef};
+use crate::signals::Signal;
+use crate::storage::{Codec, Storage, StorageBackend};
+
+thread_local! {
+    static TAB_ID: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+// random id that identifies this tab in updates
+pub fn tab_id() -> String {
+    TAB_ID.with_borrow_mut(|s| s.get_or_insert_with(|| {
+        let code = "return self.crypto?.randomUUID?.() ?? Math.random().toString(36).slice(2)";
+        Js::invoke(code, &[]).to_str().unwrap()
+    }).to_owned())
+}
+
+// value sent to other tabs, encoded as `timestamp:tab:value`
+#[derive(Debug, Clone, PartialEq)]
+struct Update { timestamp: f64, tab: String, value: String }
+
+impl Update {
+    fn encode(&self) -> String {
+        format!("{}:{}:{}", self.timestamp, self.tab, self.value)
+    }
+    fn decode(data: &str) -> Option<Self> {
+        let mut parts = data.splitn(3, ':');
+        let timestamp = parts.next()?.parse().ok()?;
+        let (tab, value) = (parts.next()?.to_owned(), parts.next()?.to_owned());
+        Some(Self { timestamp, tab, value })
+    }
+    // NOTE: last writer wins, ties are broken by the tab id so all tabs pick the same value
+    fn is_newer(&self, other: &Self) -> bool {
+        (self.timestamp, &self.tab) > (other.timestamp, &other.tab)
+    }
+}
+
+// NOTE: the last update is stored so new tabs start from it and `storage` events are used
+// to receive updates when `BroadcastChannel` isn't available in the browser
+struct Transport { key: String, channel: Option<ObjectRef>, storage: Box<dyn StorageBackend> }
+
+impl Transport {
+    fn new(name: &str, storage: impl StorageBackend + 'static) -> Self {
+        let code = "return typeof BroadcastChannel !== 'undefined' ? new BroadcastChannel({}) : undefined";
+        let channel = match Js::invoke(code, &[name.into()]) { JsValue::Ref(channel) => Some(channel), _ => None };
+        Self { key: format!("tinyweb-sync:{}", name), channel, storage: Box::new(storage) }
+    }
+    fn last(&self) -> Option<Update> {
+        Update::decode(&self.storage.get(&self.key)?)
+    }
+    fn send(&self, data: &str) {
+        self.storage.set(&self.key, data);
+        if let Some(channel) = self.channel { Js::invoke("{}.postMessage({})", &[channel.into(), data.into()]); }
+    }
+    fn listen(&self, mut cb: impl FnMut(String) + 'static) -> ObjectRef {
+        let key = self.key.to_owned();
+        let function_ref = create_callback(move |e| {
+            let code = "const e = {}; return e instanceof MessageEvent ? e.data : e.key === {} ? e.newValue ?? undefined : undefined";
+            if let JsValue::Str(data) = Js::invoke(code, &[e.into(), key.as_str().into()]) { cb(data); }
+        });
+        match self.channel {
+            Some(channel) => { Js::invoke("{}.onmessage = {}", &[channel.into(), function_ref.into()]); },
+            None => { Js::invoke("window.addEventListener('storage',{})", &[function_ref.into()]); },
+        }
+        function_ref
+    }
+    fn close(&self, function_ref: ObjectRef) {
+        match self.channel {
+            Some(channel) => { Js::invoke("{}.close()", &[channel.into()]); Js::deallocate(channel); },
+            None => { Js::invoke("window.removeEventListener('storage',{})", &[function_ref.into()]); },
+        }
+        CALLBACK_HANDLERS.with(|s| { s.borrow_mut().remove(&function_ref); });
+        Js::deallocate(function_ref);
+    }
+}
+
+// last update sent or received and whether an update from another tab is being applied
+struct SyncState { last: RefCell<Update>, is_receiving: Cell<bool> }
+
+// applies an update from another tab if it's newer than the current value
+fn receive<T: Codec + 'static>(signal: Signal<T>, state: &SyncState, data: &str) {
+    let Some(update) = Update::decode(data) else { return; };
+    if update.tab == tab_id() || !update.is_newer(&state.last.borrow()) { return; }
+    let Ok(value) = T::decode(&update.value) else { return; };
+    *state.last.borrow_mut() = update;
+
+    // NOTE: updates from other tabs aren't sent back
+    state.is_receiving.set(true);
+    let _ = signal.try_set(value);
+    state.is_receiving.set(false);
+}
+
+// starts from the last stored update, or `default` when there's none
+fn sync<T: Codec + 'static>(name: &str, default: T, storage: impl StorageBackend + 'static) -> (Signal<T>, Rc<SyncState>) {
+    let transport = Rc::new(Transport::new(name, storage));
+    let stored = transport.last().and_then(|u| Some((T::decode(&u.value).ok()?, u)));
+    let (value, last) = stored.unwrap_or_else(|| {
+        let last = Update { timestamp: 0.0, tab: String::new(), value: default.encode() };
+        (default, last)
+    });
+    let state = Rc::new(SyncState { last: RefCell::new(last), is_receiving: Cell::new(false) });
+    let signal = Signal::new(value);
+
+    // send local updates
+    let (state_clone, transport_clone, is_first) = (state.clone(), transport.clone(), Cell::new(true));
+    signal.subscribe(move || {
+        if is_first.replace(false) || state_clone.is_receiving.get() { return; }
+        let Ok(value) = signal.try_with(|v| v.encode()) else { return; };
+        let timestamp = Js::invoke("return Date.now()", &[]).to_num().unwrap();
+        let update = Update { timestamp, tab: tab_id(), value };
+        transport_clone.send(&update.encode());
+        *state_clone.last.borrow_mut() = update;
+    }).detach();
+
+    // receive updates
+    let state_clone = state.clone();
+    let function_ref = transport.listen(move |data| receive(signal, &state_clone, &data));
+    signal.on_dispose(move || transport.close(function_ref));
+
+    (signal, state)
+}
+
+impl<T: Codec + 'static> Signal<T> {
+    // signal that is kept in sync with the signals with the same name in other tabs of the same origin
+    // NOTE: the channel is closed when the signal is disposed, directly or by its scope
+    pub fn synced(name: &str, default: T) -> Self {
+        sync(name, default, Storage::Local).0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::collections::HashMap;
+
+    use crate::signals::Scope;
+
+    use super::*;
+
+    #[test]
+    fn test_update() {
+        let update = Update { timestamp: 1.0, tab: "a".to_owned(), value: "x:y".to_owned() };
+        assert_eq!(Update::decode(&update.encode()), Some(update.clone()));
+        assert_eq!(Update::decode("invalid"), None);
+        assert!(Update { timestamp: 1.0, tab: "b".to_owned(), value: String::new() }.is_newer(&update));
+        assert!(!Update { timestamp: 0.5, tab: "b".to_owned(), value: String::new() }.is_newer(&update));
+    }
+
+    #[derive(Clone, Default)]
+    struct MemoryStorage(Rc<RefCell<HashMap<String, String>>>);
+
+    impl StorageBackend for MemoryStorage {
+        fn get(&self, key: &str) -> Option<String> { self.0.borrow().get(key).cloned() }
+        fn set(&self, key: &str, value: &str) -> bool { self.0.borrow_mut().insert(key.to_owned(), value.to_owned()); true }
+        fn remove(&self, key: &str) { self.0.borrow_mut().remove(key); }
+    }
+
+    #[test]
+    fn test_synced() {
+
+        let scope = Scope::new();
+        let storage = MemoryStorage::default();
+        let (signal, state) = scope.run(|| sync("session", false, storage.clone()));
+
+        // last writer wins
+        receive(signal, &state, "2:other:true");
+        receive(signal, &state, "1:other:false");
+        assert_eq!(signal.get(), true);
+        assert_eq!(state.last.borrow().tab, "other");
+
+        // ignore own updates
+        receive(signal, &state, &format!("3:{}:false", tab_id()));
+        assert_eq!(signal.get(), true);
+
+        // send local updates equal to the last received value
+        signal.set(true);
+        assert_eq!(state.last.borrow().tab, tab_id());
+
+        // start from the last update
+        let (other, _) = sync::<bool>("session", false, storage.clone());
+        assert_eq!(other.get(), true);
+        other.dispose();
+
+        // close channel on dispose
+        scope.dispose();
+        let count = CALLBACK_HANDLERS.with(|s| s.borrow().len());
+        assert_eq!(count, 0);
+
+        // close channel when created outside a scope
+        let signal = Signal::synced("session", false);
+        signal.dispose();
+        let count = CALLBACK_HANDLERS.with(|s| s.borrow().len());
+        assert_eq!(count, 0);
+    }
+
+}
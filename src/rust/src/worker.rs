@@ -0,0 +1,173 @@
+
+use std::marker::PhantomData;
+
+use crate::callbacks::{create_callback, CALLBACK_HANDLERS};
+use crate::invoke::{Js, JsValue, ObjectRef};
+use crate::sync::mpsc;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Payload { Str(String), Buffer(Vec<u8>) }
+
+impl From<Payload> for JsValue {
+    fn from(p: Payload) -> Self {
+        match p { Payload::Str(s) => Self::Str(s), Payload::Buffer(b) => Self::Buffer(b) }
+    }
+}
+
+impl TryFrom<JsValue> for Payload {
+    type Error = String;
+
+    fn try_from(v: JsValue) -> Result<Self, Self::Error> {
+        match v {
+            JsValue::Str(s) => Ok(Self::Str(s)),
+            JsValue::Buffer(b) => Ok(Self::Buffer(b)),
+            _ => Err("Invalid payload".to_string()),
+        }
+    }
+}
+
+// messages are encoded to strings or buffers, buffers are transferred without copying
+pub trait Message: Sized {
+    fn encode(self) -> Payload;
+    fn decode(payload: Payload) -> Result<Self, String>;
+}
+
+impl Message for String {
+    fn encode(self) -> Payload { Payload::Str(self) }
+    fn decode(payload: Payload) -> Result<Self, String> {
+        match payload { Payload::Str(s) => Ok(s), _ => Err("Expected string".to_string()) }
+    }
+}
+
+impl Message for Vec<u8> {
+    fn encode(self) -> Payload { Payload::Buffer(self) }
+    fn decode(payload: Payload) -> Result<Self, String> {
+        match payload { Payload::Buffer(b) => Ok(b), _ => Err("Expected buffer".to_string()) }
+    }
+}
+
+impl Message for Payload {
+    fn encode(self) -> Payload { self }
+    fn decode(payload: Payload) -> Result<Self, String> { Ok(payload) }
+}
+
+// forwards `message` events to a channel
+fn create_message_callback() -> (ObjectRef, mpsc::Receiver<Result<Payload, String>>) {
+    let (tx, rx) = mpsc::unbounded();
+    let function_ref = create_callback(move |e| {
+        let data = Js::invoke("const d = {}.data; return d instanceof ArrayBuffer ? new Uint8Array(d) : d", &[e.into()]);
+        let _ = tx.try_send(Payload::try_from(data));
+    });
+    (function_ref, rx)
+}
+
+async fn recv<T: Message>(receiver: &mut mpsc::Receiver<Result<Payload, String>>) -> Result<T, String> {
+    let payload = receiver.recv().await.ok_or("Channel closed".to_string())??;
+    T::decode(payload)
+}
+
+// handle to a worker running the `entry` export of the current wasm module,
+// `I` is sent to the worker and `O` is received from it
+pub struct Worker<I: Message, O: Message> {
+    worker: ObjectRef,
+    function_ref: ObjectRef,
+    receiver: mpsc::Receiver<Result<Payload, String>>,
+    _marker: PhantomData<(I, O)>
+}
+
+impl<I: Message, O: Message> Worker<I, O> {
+    pub fn spawn(entry: &str) -> Self {
+        let worker = Js::invoke("return spawnWorker({})", &[entry.into()]).to_ref().unwrap();
+        let (function_ref, receiver) = create_message_callback();
+        Js::invoke("{}.onmessage = {}", &[worker.into(), function_ref.into()]);
+        Self { worker, function_ref, receiver, _marker: PhantomData }
+    }
+    pub fn send(&self, message: I) {
+        Js::invoke("postMessageTo({},{})", &[self.worker.into(), message.encode().into()]);
+    }
+    pub async fn recv(&mut self) -> Result<O, String> {
+        recv(&mut self.receiver).await
+    }
+}
+
+impl<I: Message, O: Message> Drop for Worker<I, O> {
+    fn drop(&mut self) {
+        Js::invoke("{}.terminate()", &[self.worker.into()]);
+        CALLBACK_HANDLERS.with(|s| { s.borrow_mut().remove(&self.function_ref); });
+        Js::deallocate(self.function_ref);
+        Js::deallocate(self.worker);
+    }
+}
+
+// worker side of `Worker<I, O>`, should be created in the worker entry function
+pub struct WorkerScope<I: Message, O: Message> {
+    receiver: mpsc::Receiver<Result<Payload, String>>,
+    _marker: PhantomData<(I, O)>
+}
+
+impl<I: Message, O: Message> WorkerScope<I, O> {
+    pub fn new() -> Self {
+        let (function_ref, receiver) = create_message_callback();
+        Js::invoke("setWorkerHandler({})", &[function_ref.into()]);
+        Self { receiver, _marker: PhantomData }
+    }
+    pub fn post(&self, message: O) {
+        Js::invoke("postMessageTo(self,{})", &[message.encode().into()]);
+    }
+    pub async fn recv(&mut self) -> Result<I, String> {
+        recv(&mut self.receiver).await
+    }
+}
+
+impl<I: Message, O: Message> Default for WorkerScope<I, O> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::future::Future;
+    use std::pin::pin;
+    use std::task::{Context, Poll, Waker};
+
+    use crate::callbacks::handle_callback;
+
+    use super::*;
+
+    fn poll_ready<F: Future>(future: F) -> Poll<F::Output> {
+        pin!(future).poll(&mut Context::from_waker(Waker::noop()))
+    }
+
+    #[test]
+    fn test_message() {
+
+        // encode
+        assert_eq!("hello".to_string().encode(), Payload::Str("hello".to_string()));
+        assert_eq!(vec![1, 2].encode(), Payload::Buffer(vec![1, 2]));
+
+        // decode
+        assert_eq!(String::decode(Payload::Str("hello".to_string())), Ok("hello".to_string()));
+        assert!(String::decode(Payload::Buffer(vec![])).is_err());
+        assert_eq!(Payload::try_from(JsValue::Buffer(vec![1])), Ok(Payload::Buffer(vec![1])));
+        assert!(Payload::try_from(JsValue::Undefined).is_err());
+    }
+
+    #[test]
+    fn test_worker() {
+
+        let mut worker = Worker::<String, String>::spawn("worker_main");
+        assert!(poll_ready(worker.recv()).is_pending());
+
+        // NOTE: invoke returns undefined in tests so the message is invalid
+        handle_callback(*worker.function_ref, 0);
+        assert!(matches!(poll_ready(worker.recv()), Poll::Ready(Err(_))));
+
+        // remove listener
+        drop(worker);
+        let count = CALLBACK_HANDLERS.with(|s| s.borrow().len());
+        assert_eq!(count, 0);
+    }
+
+}